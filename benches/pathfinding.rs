@@ -0,0 +1,140 @@
+#![feature(test)]
+extern crate test;
+
+use test::Bencher;
+use rand::{Rng, SeedableRng};
+use errio::{Cell, grid::Grid, levelgen::{self, LevelRng}, pathfinding};
+
+// The linear scan implementation `find_path` replaced, kept to compare against
+mod legacy {
+    use errio::{Cell, grid::Grid};
+
+    #[derive(Debug, PartialEq, Clone)]
+    struct PathNode {
+        cell: Cell,
+        g_cost: i32,
+        h_cost: i32,
+        f_cost: i32,
+        came_from: Box<Option<PathNode>>,
+    }
+
+    const DIST_BETWEEN: i32 = 10;
+
+    fn heuristic(a: &Cell, b: &Cell) -> i32 {
+        let diff_x: i32 = (a.i as i32 - b.i as i32).abs();
+        let diff_y: i32 = (a.j as i32 - b.j as i32).abs();
+        //diff_x.min(diff_y) * DIST_BETWEEN + (diff_x - diff_y).abs() * (DIST_BETWEEN as f32 * DIST_BETWEEN as f32).sqrt() as i32 // Diagonal movement
+        diff_x + diff_y
+    }
+
+    pub fn find_path(grid: &Grid, start_cell: &Cell, end_cell: &Cell) -> Option<Vec<Cell>> {
+        let start_node: PathNode = PathNode {
+            cell: start_cell.clone(),
+            g_cost: 0,
+            h_cost: 0,
+            f_cost: 0,
+            came_from: Box::new(None),
+        };
+        let end_node: PathNode = PathNode {
+            cell: end_cell.clone(),
+            g_cost: 0,
+            h_cost: 0,
+            f_cost: 0,
+            came_from: Box::new(None),
+        };
+
+        let mut open_set: Vec<PathNode> = Vec::new();
+        let mut closed_set: Vec<PathNode> = Vec::new();
+
+        open_set.push(start_node);
+
+        while open_set.len() > 0 {
+            let mut lowest_index = 0;
+            for i in 0..open_set.len() {
+                if open_set[i].f_cost < open_set[lowest_index].f_cost {
+                    lowest_index = i;
+                }
+            }
+            let current: PathNode = open_set.remove(lowest_index);
+
+            if current.cell == end_node.cell {
+                let mut path: Vec<Cell> = Vec::new();
+                let mut path_node: &PathNode = &current;
+                path.push(current.cell.clone());
+                while let Some(previous) = &*path_node.came_from {
+                    path.push(previous.cell.clone());
+                    path_node = previous;
+                }
+                return Some(path);
+            }
+
+            // Find neighbors, shut doors included like the uniform cost of the new search
+            for (_, next) in grid.passages(current.cell.i, current.cell.j) {
+                if closed_set.iter().any(|node| node.cell == *next) {
+                    continue;
+                }
+
+                let tentative_g_score = current.g_cost + DIST_BETWEEN; // Orthogonal movement
+
+                if let Some(neighbor) = open_set.iter().find(|&node| node.cell == *next) {
+                    if neighbor.g_cost <= tentative_g_score {
+                        continue;
+                    }
+                } else {
+                    let h_score = heuristic(next, &end_node.cell);
+                    let neighbor: PathNode = PathNode {
+                        cell: next.clone(),
+                        g_cost: tentative_g_score,
+                        h_cost: h_score,
+                        f_cost: tentative_g_score + h_score,
+                        came_from: Box::new(Some(current.clone())),
+                    };
+                    open_set.push(neighbor);
+                }
+            }
+            closed_set.push(current);
+        }
+        return None;
+    }
+}
+
+// Start/goal pairs spread over a generated level
+fn setup(width: usize, length: usize) -> (Grid, Vec<(Cell, Cell)>) {
+    let (grid, _) = levelgen::generate_level(width, length, 7, 3, Some(42));
+    let mut rng = LevelRng::seed_from_u64(7);
+    let pairs = (0..16).map(|_| {
+        let start = grid[rng.gen_range(0..grid.len())];
+        let end = grid[rng.gen_range(0..grid.len())];
+        (start, end)
+    }).collect();
+    (grid, pairs)
+}
+
+fn bench_path(b: &mut Bencher, width: usize, length: usize, find: fn(&Grid, &Cell, &Cell) -> Option<Vec<Cell>>) {
+    let (grid, pairs) = setup(width, length);
+    b.iter(|| {
+        for (start, end) in pairs.iter() {
+            test::black_box(find(&grid, start, end));
+        }
+    });
+}
+
+#[bench]
+fn legacy_12x16(b: &mut Bencher) {
+    bench_path(b, 12, 16, legacy::find_path);
+}
+
+#[bench]
+fn heap_12x16(b: &mut Bencher) {
+    bench_path(b, 12, 16, pathfinding::find_path);
+}
+
+#[bench]
+fn legacy_48x64(b: &mut Bencher) {
+    bench_path(b, 48, 64, legacy::find_path);
+}
+
+#[bench]
+fn heap_48x64(b: &mut Bencher) {
+    bench_path(b, 48, 64, pathfinding::find_path);
+}
@@ -1,6 +1,3 @@
-pub const MAP_SIZE_I: usize = 12;
-pub const MAP_SIZE_J: usize = 16;
-
 pub mod fonts {
     pub const MAIN_FONT: &str = "fonts/SourceCodePro-Medium.ttf";
 }
@@ -0,0 +1,113 @@
+use serde::{Serialize, Deserialize};
+use crate::{vision::VisionParams, guard::GUARD_VISION};
+
+// How hard a run is, picked in the menu before it starts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Default for Difficulty {
+    fn default() -> Self {
+        Difficulty::Normal
+    }
+}
+
+// Kind of guard, the sharper ones show up on higher floors
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GuardTier {
+    // First floor the tier is sent to
+    pub from_floor: u32,
+    pub vision: VisionParams,
+    // Index into `assets::ASTRONAUTS`
+    pub model: usize,
+}
+
+// Guard progression of a run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DifficultyProfile {
+    pub difficulty: Difficulty,
+    // Guards on a floor when it starts, and how many more every floor up brings
+    pub initial_guards: usize,
+    pub guards_per_floor: usize,
+    // More guards come in as intel goes missing
+    pub guards_per_intel: f32,
+    pub max_guards: usize,
+    // Fewest steps between the player and a guard walking in
+    pub min_spawn_steps: i32,
+    pub tiers: Vec<GuardTier>,
+}
+
+// Wider but shorter sight than a patrolling guard
+const SENTRY_VISION: VisionParams = VisionParams { range: 3.0, angle: 150.0 };
+// Sees far down corridors
+const SNIPER_VISION: VisionParams = VisionParams { range: 6.0, angle: 60.0 };
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Normal => "Normal",
+            Difficulty::Hard => "Hard",
+        }
+    }
+
+    pub fn profile(self) -> DifficultyProfile {
+        let patrol = |from_floor| GuardTier { from_floor, vision: GUARD_VISION, model: 0 };
+        let sentry = |from_floor| GuardTier { from_floor, vision: SENTRY_VISION, model: 1 };
+        let sniper = |from_floor| GuardTier { from_floor, vision: SNIPER_VISION, model: 1 };
+        match self {
+            Difficulty::Easy => DifficultyProfile {
+                difficulty: self,
+                initial_guards: 1,
+                guards_per_floor: 1,
+                guards_per_intel: 0.25,
+                max_guards: 4,
+                min_spawn_steps: 8,
+                tiers: vec![patrol(1), sentry(3)],
+            },
+            Difficulty::Normal => DifficultyProfile {
+                difficulty: self,
+                initial_guards: 2,
+                guards_per_floor: 1,
+                guards_per_intel: 0.34,
+                max_guards: 6,
+                min_spawn_steps: 6,
+                tiers: vec![patrol(1), sentry(2), sniper(3)],
+            },
+            Difficulty::Hard => DifficultyProfile {
+                difficulty: self,
+                initial_guards: 3,
+                guards_per_floor: 2,
+                guards_per_intel: 0.5,
+                max_guards: 10,
+                min_spawn_steps: 4,
+                tiers: vec![patrol(1), sentry(1), sniper(2)],
+            },
+        }
+    }
+}
+
+impl Default for DifficultyProfile {
+    fn default() -> Self {
+        Difficulty::default().profile()
+    }
+}
+
+impl DifficultyProfile {
+    // Guards a floor calls for once `intel` was picked up on it, knocked out ones included
+    pub fn guards_wanted(&self, floor: u32, intel: i32) -> usize {
+        let on_arrival = self.initial_guards + self.guards_per_floor * floor.saturating_sub(1) as usize;
+        let alerted = (self.guards_per_intel * intel.max(0) as f32).floor() as usize;
+        (on_arrival + alerted).min(self.max_guards)
+    }
+
+    // Tiers a guard on `floor` can be drawn from
+    pub fn tiers_on(&self, floor: u32) -> Vec<&GuardTier> {
+        self.tiers.iter().filter(|tier| tier.from_floor <= floor).collect()
+    }
+}
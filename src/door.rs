@@ -0,0 +1,164 @@
+use bevy::prelude::*;
+use crate::{GameState, DoorState, grid::Direction, game::{Game, GameSystem, LevelTag}, turn::{Turn, PlayMode}, noise::{self, Noise}, sim::{SimStage, SimSystem, PlayerInput}};
+
+// Action points to open or close a door, and to hack a lock
+pub const DOOR_ACTION_POINTS: u32 = 1;
+pub const HACK_ACTION_POINTS: u32 = 3;
+
+// The leaf of a door, on the east or south side of cell (i, j). Its state lives in `Game::map`
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Door {
+    pub i: usize,
+    pub j: usize,
+    pub dir: Direction,
+}
+
+// What came of the player walking into a doorway
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DoorUse {
+    // Nothing to open, go ahead and step
+    NoDoor,
+    Opened,
+    // Locked and no keycard
+    Blocked,
+    // Not enough action points left this turn
+    Waiting,
+}
+
+#[derive(Default)]
+struct DoorView {
+    // Door revision of the map the leaves were drawn for
+    drawn: Option<u32>,
+    mesh: Handle<Mesh>,
+    closed: Handle<StandardMaterial>,
+    locked: Handle<StandardMaterial>,
+}
+
+pub struct DoorPlugin;
+impl Plugin for DoorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_to_stage(SimStage, use_door.label(SimSystem::Player).label(SimSystem::Door).after(SimSystem::Move));
+    }
+}
+
+// Draws the leaves of the level's doors
+pub struct DoorViewPlugin;
+impl Plugin for DoorViewPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<DoorView>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::Game)
+                    .with_system(setup_doors.after(GameSystem::SetupLevel))
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Game)
+                    .with_system(draw_doors)
+            );
+    }
+}
+
+fn setup_doors(
+    mut commands: Commands,
+    mut view: ResMut<DoorView>,
+    game: Res<Game>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    *view = DoorView {
+        mesh: meshes.add(Mesh::from(shape::Box::new(0.8, 1.0, 0.08))),
+        closed: materials.add(Color::rgb(0.45, 0.5, 0.6).into()),
+        locked: materials.add(Color::rgb(0.7, 0.15, 0.1).into()),
+        ..Default::default()
+    };
+    for (i, j, dir) in game.map.door_edges() {
+        commands.spawn_bundle(PbrBundle {
+            mesh: view.mesh.clone(),
+            material: view.closed.clone(),
+            transform: door_transform(&game, i, j, dir),
+            ..Default::default()
+        })
+        .insert(Door { i, j, dir })
+        .insert(LevelTag);
+    }
+}
+
+// Leaf standing in the middle of the edge
+fn door_transform(game: &Game, i: usize, j: usize, dir: Direction) -> Transform {
+    let height = game.map.get(i, j).map_or(0.0, |cell| cell.height);
+    let (di, dj) = dir.offset();
+    Transform {
+        translation: Vec3::new(i as f32 + di as f32 * 0.5, height + 0.5, j as f32 + dj as f32 * 0.5),
+        rotation: Quat::from_rotation_y(dir.yaw()),
+        ..Default::default()
+    }
+}
+
+// open doors get out of the way, locked ones are red
+fn draw_doors(
+    mut view: ResMut<DoorView>,
+    game: Res<Game>,
+    mut doors: Query<(&Door, &mut Handle<StandardMaterial>, &mut Transform)>,
+) {
+    if view.drawn == Some(game.map.door_revision()) {
+        return;
+    }
+    view.drawn = Some(game.map.door_revision());
+    for (door, mut material, mut transform) in doors.iter_mut() {
+        *transform = door_transform(&game, door.i, door.j, door.dir);
+        match game.map.door(door.i, door.j, door.dir) {
+            Some(DoorState::Closed) => *material = view.closed.clone(),
+            Some(DoorState::Locked) => *material = view.locked.clone(),
+            _ => transform.scale = Vec3::ZERO,
+        }
+    }
+}
+
+// Open the door the player walks into, a keycard gets through locked ones. It can be heard
+pub fn open_door(game: &mut Game, turn: &mut Turn, noises: &mut EventWriter<Noise>, dir: Direction) -> DoorUse {
+    let (i, j) = (game.player.i, game.player.j);
+    if !game.map.is_passage(i, j, dir) {
+        return DoorUse::NoDoor;
+    }
+    match game.map.door(i, j, dir) {
+        None | Some(DoorState::Open) => DoorUse::NoDoor,
+        Some(DoorState::Locked) if !game.player.keycard => DoorUse::Blocked,
+        Some(_) if turn.spend(DOOR_ACTION_POINTS) => {
+            game.map.set_door(i, j, dir, DoorState::Open);
+            noises.send(Noise { at: (i, j), radius: noise::DOOR_NOISE });
+            DoorUse::Opened
+        },
+        Some(_) => DoorUse::Waiting,
+    }
+}
+
+// `E` opens or closes the door the player faces, `H` hacks its lock
+fn use_door(
+    mut game: ResMut<Game>,
+    mut turn: ResMut<Turn>,
+    mut noises: EventWriter<Noise>,
+    input: Res<PlayerInput>,
+) {
+    if !turn.player_can_act() {
+        return;
+    }
+    let (i, j, dir) = (game.player.i, game.player.j, game.player.facing);
+    if input.door {
+        match game.map.door(i, j, dir) {
+            Some(DoorState::Open) if turn.spend(DOOR_ACTION_POINTS) => {
+                game.map.set_door(i, j, dir, DoorState::Closed);
+            },
+            Some(DoorState::Closed) | Some(DoorState::Locked) => {
+                open_door(&mut game, &mut turn, &mut noises, dir);
+            },
+            _ => {},
+        }
+    } else if input.hack {
+        if game.map.door(i, j, dir) == Some(DoorState::Locked) && turn.spend(HACK_ACTION_POINTS) {
+            game.map.set_door(i, j, dir, DoorState::Closed);
+        }
+    }
+    if turn.mode == PlayMode::TurnBased && turn.action_points == 0 {
+        turn.end_player_turn();
+    }
+}
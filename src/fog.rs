@@ -0,0 +1,251 @@
+use std::collections::BTreeMap;
+use bevy::prelude::*;
+use serde::{Serialize, Deserialize};
+use crate::{GameState, grid::{Grid, Direction}, game::{Game, GameSystem, LevelTag}, vision::{self, VisionParams}, save::Restore, sim::{SimStage, SimSystem}};
+
+// The player looks all around
+pub const PLAYER_VISION: VisionParams = VisionParams { range: 5.0, angle: 360.0 };
+
+// What the player knows about a cell
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum FogState {
+    Unexplored,
+    // Seen before, not in view right now
+    Remembered,
+    Visible,
+}
+
+impl Default for FogState {
+    fn default() -> Self {
+        FogState::Unexplored
+    }
+}
+
+// Fog of war over a grid, laid out like its cells, plus where guards were last seen
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FogOfWar {
+    width: usize,
+    length: usize,
+    cells: Vec<FogState>,
+    // Guard id to the cell it was last seen on
+    pub last_seen: BTreeMap<usize, (usize, usize)>,
+}
+
+impl FogOfWar {
+    pub fn new(grid: &Grid) -> Self {
+        Self {
+            width: grid.width(),
+            length: grid.length(),
+            cells: vec![FogState::Unexplored; grid.len()],
+            last_seen: BTreeMap::new(),
+        }
+    }
+
+    // Unexplored for cells off the grid
+    pub fn state(&self, i: usize, j: usize) -> FogState {
+        if i < self.width && j < self.length {
+            self.cells[j * self.width + i]
+        } else {
+            FogState::Unexplored
+        }
+    }
+
+    pub fn is_visible(&self, i: usize, j: usize) -> bool {
+        self.state(i, j) == FogState::Visible
+    }
+
+    pub fn is_explored(&self, i: usize, j: usize) -> bool {
+        self.state(i, j) != FogState::Unexplored
+    }
+
+    pub fn states(&self) -> &[FogState] {
+        &self.cells
+    }
+
+    // Look around from `origin`: what was in view is only remembered, what is in view now becomes visible
+    pub fn reveal(&mut self, grid: &Grid, origin: (usize, usize), params: &VisionParams) {
+        if grid.width() != self.width || grid.length() != self.length {
+            *self = FogOfWar::new(grid);
+        }
+        for state in self.cells.iter_mut().filter(|state| **state == FogState::Visible) {
+            *state = FogState::Remembered;
+        }
+        for (i, j) in vision::visible_cells(grid, origin, Direction::North, params) {
+            self.cells[j * self.width + i] = FogState::Visible;
+        }
+    }
+}
+
+// Where the fog was last revealed from, and the door revision of the map then
+#[derive(Default)]
+struct RevealedFrom(Option<((usize, usize), u32)>);
+
+// Tiles drawn over the cells the player doesn't see
+#[derive(Default)]
+struct FogView {
+    tiles: Vec<Entity>,
+    drawn: Vec<Option<FogState>>,
+    ghosts: Vec<Entity>,
+    cover: Handle<Mesh>,
+    tint: Handle<Mesh>,
+    ghost: Handle<Mesh>,
+    unexplored: Handle<StandardMaterial>,
+    remembered: Handle<StandardMaterial>,
+    last_seen: Handle<StandardMaterial>,
+}
+
+#[derive(Component)]
+struct FogTile;
+
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum FogSystem {
+    // What the player sees this step is known
+    Reveal,
+}
+
+pub struct FogPlugin;
+impl Plugin for FogPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<FogOfWar>()
+            .init_resource::<RevealedFrom>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::Game)
+                    .with_system(setup_fog.after(GameSystem::SetupLevel))
+            )
+            .add_system_to_stage(SimStage, reveal_fog.label(FogSystem::Reveal).after(SimSystem::Guards));
+    }
+}
+
+// Draws the fog over the level
+pub struct FogViewPlugin;
+impl Plugin for FogViewPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<FogView>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::Game)
+                    .with_system(setup_fog_view.after(GameSystem::SetupLevel))
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Game)
+                    .with_system(draw_fog)
+            );
+    }
+}
+
+fn setup_fog(
+    mut fog: ResMut<FogOfWar>,
+    mut from: ResMut<RevealedFrom>,
+    mut restore: ResMut<Restore>,
+    game: Res<Game>,
+) {
+    *fog = restore.fog.take().unwrap_or_else(|| FogOfWar::new(&game.map));
+    from.0 = None;
+}
+
+fn setup_fog_view(
+    mut commands: Commands,
+    mut view: ResMut<FogView>,
+    game: Res<Game>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let mut material = |color: Color| materials.add(StandardMaterial {
+        base_color: color,
+        unlit: true,
+        ..Default::default()
+    });
+    *view = FogView {
+        cover: meshes.add(Mesh::from(shape::Box::new(1.0, 1.5, 1.0))),
+        tint: meshes.add(Mesh::from(shape::Plane { size: 1.0 })),
+        ghost: meshes.add(Mesh::from(shape::Box::new(0.3, 0.6, 0.3))),
+        unexplored: material(Color::BLACK),
+        remembered: material(Color::rgb(0.12, 0.12, 0.18)),
+        last_seen: material(Color::rgb(0.5, 0.15, 0.15)),
+        ..Default::default()
+    };
+    for cell in game.map.iter() {
+        let tile = commands.spawn_bundle(PbrBundle {
+            mesh: view.cover.clone(),
+            material: view.unexplored.clone(),
+            transform: Transform::from_xyz(cell.i as f32, cell.height + 0.75, cell.j as f32),
+            ..Default::default()
+        })
+        .insert(FogTile)
+        .insert(LevelTag)
+        .id();
+        view.tiles.push(tile);
+    }
+    view.drawn = vec![Some(FogState::Unexplored); game.map.len()];
+}
+
+fn reveal_fog(mut fog: ResMut<FogOfWar>, mut revealed: ResMut<RevealedFrom>, game: Res<Game>) {
+    // look again after moving or when a door opened or closed
+    let from = ((game.player.i, game.player.j), game.map.door_revision());
+    if revealed.0 == Some(from) {
+        return;
+    }
+    revealed.0 = Some(from);
+    fog.reveal(&game.map, from.0, &PLAYER_VISION);
+}
+
+// Black boxes over unexplored cells, a dim floor over remembered ones, nothing over what's in view.
+// Guards out of view are marked where they were last seen
+fn draw_fog(
+    mut commands: Commands,
+    fog: Res<FogOfWar>,
+    mut view: ResMut<FogView>,
+    game: Res<Game>,
+    mut tiles: Query<(&mut Handle<Mesh>, &mut Handle<StandardMaterial>, &mut Transform), With<FogTile>>,
+) {
+    if !fog.is_changed() {
+        return;
+    }
+    let view = &mut *view;
+    for (index, &state) in fog.states().iter().enumerate() {
+        if view.drawn.get(index) == Some(&Some(state)) {
+            continue;
+        }
+        let (cell, entity) = match (game.map.get(index % game.map.width(), index / game.map.width()), view.tiles.get(index)) {
+            (Some(cell), Some(&entity)) => (cell, entity),
+            _ => continue,
+        };
+        if let Ok((mut mesh, mut material, mut transform)) = tiles.get_mut(entity) {
+            let position = Vec3::new(cell.i as f32, cell.height, cell.j as f32);
+            match state {
+                FogState::Unexplored => {
+                    *mesh = view.cover.clone();
+                    *material = view.unexplored.clone();
+                    *transform = Transform::from_translation(position + Vec3::Y * 0.75);
+                },
+                FogState::Remembered => {
+                    *mesh = view.tint.clone();
+                    *material = view.remembered.clone();
+                    *transform = Transform::from_translation(position + Vec3::Y * 0.01);
+                },
+                FogState::Visible => {
+                    *transform = Transform::from_translation(position).with_scale(Vec3::ZERO);
+                },
+            }
+        }
+        view.drawn[index] = Some(state);
+    }
+
+    for entity in view.ghosts.drain(..) {
+        commands.entity(entity).despawn();
+    }
+    // a last seen spot in plain view shows the guard has moved on
+    for &(i, j) in fog.last_seen.values().filter(|&&(i, j)| !fog.is_visible(i, j)) {
+        let height = game.map.get(i, j).map_or(0.0, |cell| cell.height);
+        let ghost = commands.spawn_bundle(PbrBundle {
+            mesh: view.ghost.clone(),
+            material: view.last_seen.clone(),
+            transform: Transform::from_xyz(i as f32, height + 0.3, j as f32),
+            ..Default::default()
+        })
+        .insert(LevelTag)
+        .id();
+        view.ghosts.push(ghost);
+    }
+}
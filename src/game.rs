@@ -1,49 +1,112 @@
 use bevy::{prelude::*, core::FixedTimestep, render::render_resource::std140::Std140};
-use crate::{GameState, Cell, levelgen, guard::GuardPlugin, consts::{fonts, assets, MAP_SIZE_I, MAP_SIZE_J}, despawn_entities};
-use rand::Rng;
-use std::{fs::File, io::Read};
-use std::io::{Write, BufReader, BufRead, Error};
-
-const RESET_POS: [f32; 3] = [
-    MAP_SIZE_I as f32 / 2.0,
-    0.0,
-    MAP_SIZE_J as f32 / 2.0,
-];
+use crate::{GameState, Cell, Terrain, grid::{Grid, Direction}, levelgen::{self, LevelRng}, levelfile::{self, LevelFile}, pathfinding::{self, CostModel, TerrainCost}, guard::{GuardPlugin, GuardViewPlugin}, turn::{self, Turn, TurnPlugin, PlayMode, TurnPhase}, route::{RoutePlugin, RoutePreview}, noise::{self, Noise}, fog::{FogPlugin, FogViewPlugin, FogOfWar, FogSystem}, door::{self, DoorPlugin, DoorViewPlugin, DoorUse}, difficulty::Difficulty, settings::Settings, leaderboard::{self, Leaderboard, ScoreEntry, MAX_NAME_LENGTH}, save::{Restore, RunSnapshot, RunState}, sim::{self, SimPlugin, SimStage, SimSystem, SimClock, PlayerInput}, replay::Playback, consts::{fonts, assets}, despawn_entities};
+use rand::{Rng, SeedableRng, prelude::thread_rng};
+use serde::{Serialize, Deserialize};
+use std::{path::PathBuf, time::Duration};
 
 const MOVE_DELAY: f32 = 0.3;
+const RUN_DELAY: f32 = 0.15;
+// Seconds before new intel shows up in real time, in turn-based mode it comes with the next turn
+const BONUS_DELAY: f32 = 5.0;
+// Fewest steps between the player and newly spawned intel
+const BONUS_MIN_STEPS: i32 = 4;
+// Glow of intel the player has seen
+const BONUS_LIGHT: f32 = 5.0;
 const CAMERA_OFFSET: [f32; 3] = [-5.0, 10.0, 1.0];
+// Intel needed on the first floor before the elevator opens, every floor up asks for one more
+pub const INTEL_TO_EXTRACT: i32 = 3;
+// Cells a generated floor grows by on each side per floor, up to a cap
+const FLOOR_GROWTH: usize = 2;
+const MAX_FLOOR_WIDTH: usize = 24;
+const MAX_FLOOR_LENGTH: usize = 32;
 
+// The simulation of a run: the level, the player, the guards and the score. It needs no window,
+// assets or input devices, so it also runs under `MinimalPlugins` with the input handed in
 pub struct GamePlugin;
 impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
         app
             .init_resource::<Game>()
+            .init_resource::<GameRng>()
+            .init_resource::<LevelConfig>()
+            .init_resource::<Difficulty>()
+            .init_resource::<Restore>()
+            .init_resource::<Playback>()
+            .init_resource::<FloorStart>()
+            .add_event::<Noise>()
+            .add_plugin(SimPlugin)
+            .add_plugin(TurnPlugin)
             .add_plugin(GuardPlugin)
+            .add_plugin(FogPlugin)
+            .add_plugin(DoorPlugin)
+            .add_system_set(
+                SystemSet::on_enter(GameState::Game)
+                .with_system(setup_level.label(GameSystem::SetupLevel))
+            )
+            .add_system_set_to_stage(
+                SimStage,
+                SystemSet::new()
+                .with_system(remember_floor_start.after(SimSystem::Input).before(SimSystem::Player))
+                .with_system(move_player.label(SimSystem::Player).label(SimSystem::Move).after(SimSystem::Input))
+                .with_system(extract.label(SimSystem::Level).after(SimSystem::Player))
+                .with_system(spawn_bonus.label(SimSystem::Level).after(extract))
+                .with_system(discover_bonus.after(FogSystem::Reveal))
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::NextFloor)
+                .with_system(next_floor)
+            );
+    }
+}
+
+// What the player sees and does: the level's models, the camera, the keyboard and mouse, the score
+// and the game over screen
+pub struct GameViewPlugin;
+impl Plugin for GameViewPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<LevelView>()
+            .init_resource::<Leaderboard>()
+            .init_resource::<GameOverScreen>()
+            .add_plugin(GuardViewPlugin)
+            .add_plugin(FogViewPlugin)
+            .add_plugin(DoorViewPlugin)
+            .add_plugin(RoutePlugin)
             .add_system_set(
                 SystemSet::on_enter(GameState::Game)
-                .with_system(setup_cameras)
-                .with_system(setup)
-                .with_system(setup_level)
+                .with_system(setup_cameras.after(GameSystem::SetupLevel))
+                .with_system(setup.after(GameSystem::SetupLevel))
+                .with_system(spawn_level.after(GameSystem::SetupLevel))
             )
             .add_system_set(
                 SystemSet::on_update(GameState::Game)
-                .with_system(move_player)
-                .with_system(focus_camera)
-                .with_system(rotate_bonus)
+                .with_system(sim::read_keys)
+                .with_system(place_player)
+                .with_system(focus_camera.after(place_player))
+                .with_system(draw_bonus)
+                .with_system(rotate_bonus.after(draw_bonus))
                 .with_system(scoreboard_system)
+                .with_system(export_level)
+                .with_system(draw_exit)
             )
             .add_system_set(
-                SystemSet::new()
-                .with_run_criteria(FixedTimestep::step(5.0))
-                .with_system(spawn_bonus)
-           )
+                SystemSet::on_enter(GameState::NextFloor)
+                .with_system(despawn_entities::<LevelTag>)
+            )
+            // quit from the pause menu
+            .add_system_set(
+                SystemSet::on_enter(GameState::Menu)
+                .with_system(despawn_entities::<LevelTag>)
+            )
             .add_system_set(
                 SystemSet::on_enter(GameState::GameOver)
                 .with_system(display_score)
             )
             .add_system_set(
                 SystemSet::on_update(GameState::GameOver)
-                .with_system(gameover_keyboard)
+                .with_system(name_entry)
+                .with_system(gameover_keyboard.after(name_entry))
+                .with_system(draw_gameover.after(name_entry))
             )
             .add_system_set(
                 SystemSet::on_exit(GameState::GameOver)
@@ -54,71 +117,294 @@ impl Plugin for GamePlugin {
     }
 }
 
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum GameSystem {
+    SetupLevel,
+}
+
+// Everything spawned for a level. It is all despawned when the level is left, so a setup system
+// builds the next level's entities from scratch and only resets the handles it keeps in resources
 #[derive(Component)]
-struct LevelTag;
+pub(crate) struct LevelTag;
 
 #[derive(Component)]
 struct GameOverTag;
 
 #[derive(Default)]
 pub struct Game {
-    pub map: Vec<Cell>,
+    pub map: Grid,
     pub player: Player,
     bonus: Bonus,
     pub score: i32,
-    camera_should_focus: Vec3,
-    camera_is_focus: Vec3,
+    // Seed of the run, each floor is built from it
+    pub seed: u64,
+    // Picked when the run started, a change in the settings waits for the next run
+    pub difficulty: Difficulty,
+    pub spawn: (usize, usize),
+    pub exit: (usize, usize),
+    // Floors go up from 1, `floor_intel` is what was picked up on the current one
+    pub floor: u32,
+    pub floor_intel: i32,
+    // Set when the player takes the elevator, the next level is one floor up instead of a new run
+    advancing: bool,
+    pub arrival: Arrival,
+    // Guard routes and intel spots of the level, used in order and extended with random ones when they run out
+    pub patrols: Vec<Vec<(usize, usize)>>,
+    pub bonus_spots: Vec<(usize, usize)>,
+}
+
+// How the current floor was reached
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arrival {
+    NewRun,
+    Elevator,
+    // Picked up from a saved run
+    Continued,
+}
+
+impl Default for Arrival {
+    fn default() -> Self {
+        Arrival::NewRun
+    }
+}
+
+// How the next level is built. A fixed `seed` replays the same run, `None` rolls a new one each time
+pub struct LevelConfig {
+    pub seed: Option<u64>,
+    // Curated level to play instead of a generated one, it brings its own size
+    pub file: Option<PathBuf>,
+    pub width: usize,
+    pub length: usize,
+}
+
+impl Default for LevelConfig {
+    fn default() -> Self {
+        Self {
+            seed: None,
+            file: None,
+            width: 12,
+            length: 16,
+        }
+    }
+}
+
+// Shared random source of the run. Seeded together with the level so guards and intel replay too
+pub struct GameRng(pub LevelRng);
+
+// The run as it was on the first step of the current floor, restarting the level goes back to it
+#[derive(Default)]
+pub struct FloorStart(pub Option<RunSnapshot>);
+
+impl FloorStart {
+    // Play the floor again from its first step. The level is torn down and built again on the way,
+    // false when it hasn't taken a step yet
+    pub fn restart(&self, restore: &mut Restore, state: &mut State<GameState>) -> bool {
+        match &self.0 {
+            Some(start) if state.replace(GameState::NextFloor).is_ok() => {
+                *restore = start.clone().into();
+                true
+            },
+            _ => false,
+        }
+    }
+}
+
+// Plain copy of the run's progress, for saved runs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GameSnapshot {
+    pub map: Grid,
+    pub player: PlayerSnapshot,
+    pub score: i32,
+    pub seed: u64,
+    pub difficulty: Difficulty,
+    pub floor: u32,
+    pub floor_intel: i32,
+    pub spawn: (usize, usize),
+    pub exit: (usize, usize),
+    pub patrols: Vec<Vec<(usize, usize)>>,
+    pub bonus_spots: Vec<(usize, usize)>,
+    pub bonus: BonusSnapshot,
+    pub rng: LevelRng,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlayerSnapshot {
+    pub i: usize,
+    pub j: usize,
+    pub facing: Direction,
+    pub keycard: bool,
+    pub route: Vec<(usize, usize)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BonusSnapshot {
+    // Intel lying on the floor at `i`, `j`
+    pub active: bool,
+    pub i: usize,
+    pub j: usize,
+    pub spawned: usize,
+    pub collected_on: u32,
+    pub discovered: bool,
+    // Seconds into the wait for the next intel
+    pub respawn: f32,
+}
+
+impl Game {
+    pub fn snapshot(&self, rng: &GameRng) -> GameSnapshot {
+        GameSnapshot {
+            map: self.map.clone(),
+            player: PlayerSnapshot {
+                i: self.player.i,
+                j: self.player.j,
+                facing: self.player.facing,
+                keycard: self.player.keycard,
+                route: self.player.route.clone(),
+            },
+            score: self.score,
+            seed: self.seed,
+            difficulty: self.difficulty,
+            floor: self.floor,
+            floor_intel: self.floor_intel,
+            spawn: self.spawn,
+            exit: self.exit,
+            patrols: self.patrols.clone(),
+            bonus_spots: self.bonus_spots.clone(),
+            bonus: BonusSnapshot {
+                active: self.bonus.active,
+                i: self.bonus.i,
+                j: self.bonus.j,
+                spawned: self.bonus.spawned,
+                collected_on: self.bonus.collected_on,
+                discovered: self.bonus.discovered,
+                respawn: self.bonus.respawn.elapsed_secs(),
+            },
+            rng: rng.0.clone(),
+        }
+    }
+
+    // Take the saved progress back, the level's models are spawned separately
+    fn restore(&mut self, saved: GameSnapshot) {
+        self.map = saved.map;
+        self.player.i = saved.player.i;
+        self.player.j = saved.player.j;
+        self.player.facing = saved.player.facing;
+        self.player.keycard = saved.player.keycard;
+        self.player.route = saved.player.route;
+        self.score = saved.score;
+        self.seed = saved.seed;
+        self.difficulty = saved.difficulty;
+        self.floor = saved.floor;
+        self.floor_intel = saved.floor_intel;
+        self.spawn = saved.spawn;
+        self.exit = saved.exit;
+        self.patrols = saved.patrols;
+        self.bonus_spots = saved.bonus_spots;
+        self.bonus = Bonus {
+            active: saved.bonus.active,
+            i: saved.bonus.i,
+            j: saved.bonus.j,
+            spawned: saved.bonus.spawned,
+            respawn: Timer::from_seconds(BONUS_DELAY, true),
+            collected_on: saved.bonus.collected_on,
+            discovered: saved.bonus.discovered,
+        };
+        self.bonus.respawn.set_elapsed(Duration::from_secs_f32(saved.bonus.respawn));
+    }
+
+    pub fn intel_required(&self) -> i32 {
+        INTEL_TO_EXTRACT + self.floor.saturating_sub(1) as i32
+    }
+
+    // Whether stepping on the exit ends the floor
+    pub fn can_extract(&self) -> bool {
+        self.floor_intel >= self.intel_required()
+    }
+
+    // Where the intel lies, while there is some to pick up
+    pub fn bonus(&self) -> Option<(usize, usize)> {
+        if self.bonus.active {
+            Some((self.bonus.i, self.bonus.j))
+        } else {
+            None
+        }
+    }
+}
+
+impl Default for GameRng {
+    fn default() -> Self {
+        Self(LevelRng::seed_from_u64(0))
+    }
 }
 
 #[derive(Default)]
 struct Bonus {
-    entity: Option<Entity>,
+    // Lying on the floor at `i`, `j`
+    active: bool,
     i: usize,
     j: usize,
-    handle: Handle<Scene>,
+    spawned: usize,
+    respawn: Timer,
+    // Turn the last intel was picked up on
+    collected_on: u32,
+    // Seen by the player since it was spawned, hidden in the fog until then
+    discovered: bool,
+}
+
+// Models of the level that follow the simulation, and where the camera looks
+#[derive(Default)]
+struct LevelView {
+    player: Option<Entity>,
+    // Pad on the exit cell, lit up once enough intel is collected
+    exit: Option<Entity>,
+    locked: Handle<StandardMaterial>,
+    open: Handle<StandardMaterial>,
+    bonus_scene: Handle<Scene>,
+    // Model of the intel on the floor and its light, with the count of intel spawned when it was put there
+    bonus: Option<(Entity, usize)>,
+    bonus_light: Option<Entity>,
+    camera_should_focus: Vec3,
+    camera_is_focus: Vec3,
 }
 
 #[derive(Default)]
 pub struct Player {
-    entity: Option<Entity>,
     pub i: usize,
     pub j: usize,
     move_cooldown: Timer,
+    // Cells still to walk after a click, next step last
+    pub route: Vec<(usize, usize)>,
+    pub facing: Direction,
+    // Taken from a knocked out guard, opens locked doors
+    pub keycard: bool,
 }
 
 fn setup_cameras(
     mut commands: Commands,
-    mut game: ResMut<Game>,
+    mut view: ResMut<LevelView>,
+    game: Res<Game>,
 ) {
-    game.camera_should_focus = Vec3::from(RESET_POS);
-    game.camera_is_focus = game.camera_should_focus;
+    view.camera_should_focus = map_center(&game.map);
+    view.camera_is_focus = view.camera_should_focus;
     commands.spawn_bundle(PerspectiveCameraBundle {
-        transform: Transform::from_xyz(
-                       (MAP_SIZE_I as f32 / 2.0) + CAMERA_OFFSET[0],
-                       CAMERA_OFFSET[1],
-                       MAP_SIZE_J as f32 / 2.0 + CAMERA_OFFSET[2],
-                       )
-            .looking_at(game.camera_is_focus, Vec3::Y),
+        transform: Transform::from_translation(view.camera_is_focus + Vec3::from_slice(&CAMERA_OFFSET))
+            .looking_at(view.camera_is_focus, Vec3::Y),
         ..Default::default()
-    });
+    }).insert(LevelTag);
 }
 
 fn setup(
     mut commands: Commands,
-    mut game: ResMut<Game>,
+    mut view: ResMut<LevelView>,
+    game: Res<Game>,
+    settings: Res<Settings>,
     asset_server: Res<AssetServer>,
 ) {
-    game.score = 0;
-    game.player.i = MAP_SIZE_I / 2;
-    game.player.j = MAP_SIZE_J / 2;
-    game.player.move_cooldown = Timer::from_seconds(MOVE_DELAY, false);
-
-    game.player.entity = Some(
+    view.player = Some(
         commands
             .spawn_bundle((
                     Transform {
                         translation: Vec3::new(game.player.i as f32, 0.0, game.player.j as f32),
-                        rotation: Quat::from_rotation_y(-std::f32::consts::FRAC_PI_2),
+                        rotation: Quat::from_rotation_y(game.player.facing.yaw()),
                         ..Default::default()
                     },
                     GlobalTransform::identity(),
@@ -129,7 +415,6 @@ fn setup(
             .insert(LevelTag)
             .id(),
         );
-    game.bonus = Bonus::default();
     // Spawn lights
     let half_size: f32 = 4.0;
     commands.spawn_bundle(DirectionalLightBundle {
@@ -141,7 +426,7 @@ fn setup(
         directional_light: DirectionalLight {
             color: Color::WHITE,
             illuminance: 320.0,
-            shadows_enabled: false,
+            shadows_enabled: settings.display.shadows,
             shadow_projection: OrthographicProjection {
                 left: -half_size,
                 right: half_size,
@@ -156,9 +441,6 @@ fn setup(
         ..Default::default()
     }).insert(LevelTag);
 
-    // load the scene for the intel
-    game.bonus.handle = asset_server.load(assets::BONUS);
-
     // scoreboard
     commands.spawn_bundle(TextBundle {
         text: Text::with_section(
@@ -185,23 +467,161 @@ fn setup(
 
 
 fn setup_level(
-    mut commands: Commands,
     mut game: ResMut<Game>,
+    mut rng: ResMut<GameRng>,
+    mut restore: ResMut<Restore>,
+    mut floor_start: ResMut<FloorStart>,
+    config: Res<LevelConfig>,
+    difficulty: Res<Difficulty>,
+) {
+    floor_start.0 = None;
+    // a saved run picks up where it was left
+    if let Some(saved) = restore.game.take() {
+        rng.0 = saved.rng.clone();
+        game.restore(saved);
+        game.arrival = Arrival::Continued;
+    } else {
+        // a new run starts on the first floor, taking the elevator carries the score and keycard one floor up
+        if game.advancing {
+            game.floor += 1;
+            game.arrival = Arrival::Elevator;
+        } else {
+            game.arrival = Arrival::NewRun;
+            game.floor = 1;
+            game.score = 0;
+            game.player.keycard = false;
+            game.seed = config.seed.unwrap_or_else(|| thread_rng().gen());
+            game.difficulty = *difficulty;
+        }
+        game.floor_intel = 0;
+        let floor = game.floor;
+        rng.0 = levelgen::floor_rng(game.seed, floor);
+        let (width, length) = floor_size(&config, floor);
+        // a curated level is the first floor, the ones above are generated
+        let level: LevelFile = match config.file.as_ref().filter(|_| floor == 1).map(|path| (path, levelfile::load_level(path))) {
+            Some((_, Ok(level))) => level,
+            Some((path, Err(err))) => {
+                error!("{}: {}, generating a level instead", path.display(), err);
+                generated_level(width, length, &mut rng.0)
+            },
+            None => generated_level(width, length, &mut rng.0),
+        };
+        game.map = Grid::new(level.width, level.length, level.cells);
+        game.exit = level.exit.unwrap_or_else(|| levelgen::exit_point(&game.map, level.spawn));
+        game.spawn = level.spawn;
+        game.player.i = level.spawn.0;
+        game.player.j = level.spawn.1;
+        game.player.route.clear();
+        game.player.facing = Direction::East;
+        game.patrols = level.patrols;
+        game.bonus_spots = level.bonuses;
+        game.bonus = Bonus {
+            respawn: Timer::from_seconds(BONUS_DELAY, true),
+            ..Default::default()
+        };
+    }
+    game.advancing = false;
+    game.player.move_cooldown = Timer::from_seconds(MOVE_DELAY, false);
+}
+
+// Floor, walls and doorways of the level, with the exit pad and the intel lying around in a saved run
+fn spawn_level(
+    mut commands: Commands,
+    mut view: ResMut<LevelView>,
+    game: Res<Game>,
     asset_server: Res<AssetServer>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
 ) {
     // Load assets
     let floor_scene: Handle<Scene> = asset_server.load(assets::FLOOR);
     let wall_scene: Handle<Scene> = asset_server.load(assets::WALL);
     let door_scene: Handle<Scene> = asset_server.load(assets::DOOR);
-    //
-    let map: Vec<Cell> = levelgen::generate_level(MAP_SIZE_I, MAP_SIZE_J, 7, 3);
-    for j in 0..MAP_SIZE_J {
-        for i in 0..MAP_SIZE_I {
-            let cell: &Cell = &map[MAP_SIZE_I * j + i];
-            // Spawn floor
+    let terrain_mesh: Handle<Mesh> = meshes.add(Mesh::from(shape::Plane { size: 0.9 }));
+    let mut terrain_material = |color: Color| materials.add(StandardMaterial {
+        base_color: color,
+        unlit: true,
+        ..Default::default()
+    });
+    let shadow_material = terrain_material(Color::rgb(0.05, 0.05, 0.1));
+    let vent_material = terrain_material(Color::rgb(0.3, 0.32, 0.35));
+    let hazard_material = terrain_material(Color::rgb(0.9, 0.5, 0.0));
+    let exit = game.exit;
+    let exit_height = game.map.get(exit.0, exit.1).map_or(0.0, |cell| cell.height);
+    view.locked = terrain_material(Color::rgb(0.3, 0.1, 0.1));
+    view.open = terrain_material(Color::rgb(0.1, 0.9, 0.3));
+    view.bonus_scene = asset_server.load(assets::BONUS);
+    view.bonus = None;
+    view.bonus_light = None;
+    view.exit = Some(commands.spawn_bundle(PbrBundle {
+        mesh: meshes.add(Mesh::from(shape::Plane { size: 0.8 })),
+        material: view.locked.clone(),
+        transform: Transform::from_xyz(exit.0 as f32, exit_height + 0.03, exit.1 as f32),
+        ..Default::default()
+    }).insert(LevelTag).id());
+    for cell in game.map.iter() {
+        let (i, j) = (cell.i, cell.j);
+        // Spawn floor
+        commands.spawn_bundle((
+                Transform {
+                    translation: Vec3::new(i as f32, cell.height, j as f32),
+                    rotation: Quat::from_rotation_y(0.0_f32.to_radians()),
+                    scale: Vec3::new(1.0, 1.0, 1.0),
+                },
+                GlobalTransform::identity(),
+                ))
+            .insert(LevelTag)
+            .with_children(|parent| {
+                parent.spawn_scene(floor_scene.clone());
+            });
+        // Mark special terrain on top of the floor
+        let material = match cell.terrain {
+            Terrain::Floor => None,
+            Terrain::Shadow => Some(shadow_material.clone()),
+            Terrain::Vent => Some(vent_material.clone()),
+            Terrain::Hazard => Some(hazard_material.clone()),
+        };
+        if let Some(material) = material {
+            commands.spawn_bundle(PbrBundle {
+                mesh: terrain_mesh.clone(),
+                material,
+                transform: Transform::from_xyz(i as f32, cell.height + 0.02, j as f32),
+                ..Default::default()
+            }).insert(LevelTag);
+        }
+        // Spawn walls
+        if i == 0 {
             commands.spawn_bundle((
                     Transform {
-                        translation: Vec3::new(i as f32, cell.height, j as f32),
+                        translation: Vec3::new(i as f32, 0.0, j as f32),
+                        rotation: Quat::from_rotation_y(270.0_f32.to_radians()),
+                        scale: Vec3::new(1.0, 1.0, 1.0),
+                    },
+                    GlobalTransform::identity(),
+                    ))
+                .insert(LevelTag)
+                .with_children(|parent| {
+                    parent.spawn_scene(wall_scene.clone());
+                });
+        }
+        if j == 0 {
+            commands.spawn_bundle((
+                    Transform {
+                        translation: Vec3::new(i as f32, 0.0, j as f32),
+                        rotation: Quat::from_rotation_y(180.0_f32.to_radians()),
+                        scale: Vec3::new(1.0, 1.0, 1.0),
+                    },
+                    GlobalTransform::identity(),
+                    ))
+                .insert(LevelTag)
+                .with_children(|parent| {
+                    parent.spawn_scene(wall_scene.clone());
+                });
+        }
+        if !cell.open_sides[2] {
+            commands.spawn_bundle((
+                    Transform {
+                        translation: Vec3::new(i as f32, 0.0, j as f32),
                         rotation: Quat::from_rotation_y(0.0_f32.to_radians()),
                         scale: Vec3::new(1.0, 1.0, 1.0),
                     },
@@ -209,273 +629,516 @@ fn setup_level(
                     ))
                 .insert(LevelTag)
                 .with_children(|parent| {
-                    parent.spawn_scene(floor_scene.clone());
+                    parent.spawn_scene(wall_scene.clone());
+                });
+        } else if cell.doors[2] {
+            commands.spawn_bundle((
+                    Transform {
+                        translation: Vec3::new(i as f32, 0.0, j as f32),
+                        rotation: Quat::from_rotation_y(0.0_f32.to_radians()),
+                        scale: Vec3::new(1.0, 1.0, 1.0),
+                    },
+                    GlobalTransform::identity(),
+                    ))
+                .insert(LevelTag)
+                .with_children(|parent| {
+                    parent.spawn_scene(door_scene.clone());
+                });
+        }
+        if !cell.open_sides[1] {
+            commands.spawn_bundle((
+                    Transform {
+                        translation: Vec3::new(i as f32, 0.0, j as f32),
+                        rotation: Quat::from_rotation_y(90.0_f32.to_radians()),
+                        scale: Vec3::new(1.0, 1.0, 1.0),
+                    },
+                    GlobalTransform::identity(),
+                    ))
+                .insert(LevelTag)
+                .with_children(|parent| {
+                    parent.spawn_scene(wall_scene.clone());
+                });
+        } else if cell.doors[1] {
+            commands.spawn_bundle((
+                    Transform {
+                        translation: Vec3::new(i as f32, 0.0, j as f32),
+                        rotation: Quat::from_rotation_y(90.0_f32.to_radians()),
+                        scale: Vec3::new(1.0, 1.0, 1.0),
+                    },
+                    GlobalTransform::identity(),
+                    ))
+                .insert(LevelTag)
+                .with_children(|parent| {
+                    parent.spawn_scene(door_scene.clone());
                 });
-            // Spawn walls
-            if i == 0 {
-                commands.spawn_bundle((
-                        Transform {
-                            translation: Vec3::new(i as f32, 0.0, j as f32),
-                            rotation: Quat::from_rotation_y(270.0_f32.to_radians()),
-                            scale: Vec3::new(1.0, 1.0, 1.0),
-                        },
-                        GlobalTransform::identity(),
-                        ))
-                    .insert(LevelTag)
-                    .with_children(|parent| {
-                        parent.spawn_scene(wall_scene.clone());
-                    });
-            }
-            if j == 0 {
-                commands.spawn_bundle((
-                        Transform {
-                            translation: Vec3::new(i as f32, 0.0, j as f32),
-                            rotation: Quat::from_rotation_y(180.0_f32.to_radians()),
-                            scale: Vec3::new(1.0, 1.0, 1.0),
-                        },
-                        GlobalTransform::identity(),
-                        ))
-                    .insert(LevelTag)
-                    .with_children(|parent| {
-                        parent.spawn_scene(wall_scene.clone());
-                    });
-            }
-            if !cell.open_sides[2] {
-                commands.spawn_bundle((
-                        Transform {
-                            translation: Vec3::new(i as f32, 0.0, j as f32),
-                            rotation: Quat::from_rotation_y(0.0_f32.to_radians()),
-                            scale: Vec3::new(1.0, 1.0, 1.0),
-                        },
-                        GlobalTransform::identity(),
-                        ))
-                    .insert(LevelTag)
-                    .with_children(|parent| {
-                        parent.spawn_scene(wall_scene.clone());
-                    });
-            } else if cell.doors[2] {
-                commands.spawn_bundle((
-                        Transform {
-                            translation: Vec3::new(i as f32, 0.0, j as f32),
-                            rotation: Quat::from_rotation_y(0.0_f32.to_radians()),
-                            scale: Vec3::new(1.0, 1.0, 1.0),
-                        },
-                        GlobalTransform::identity(),
-                        ))
-                    .insert(LevelTag)
-                    .with_children(|parent| {
-                        parent.spawn_scene(door_scene.clone());
-                    });
-            }
-            if !cell.open_sides[1] {
-                commands.spawn_bundle((
-                        Transform {
-                            translation: Vec3::new(i as f32, 0.0, j as f32),
-                            rotation: Quat::from_rotation_y(90.0_f32.to_radians()),
-                            scale: Vec3::new(1.0, 1.0, 1.0),
-                        },
-                        GlobalTransform::identity(),
-                        ))
-                    .insert(LevelTag)
-                    .with_children(|parent| {
-                        parent.spawn_scene(wall_scene.clone());
-                    });
-            } else if cell.doors[1] {
-                commands.spawn_bundle((
-                        Transform {
-                            translation: Vec3::new(i as f32, 0.0, j as f32),
-                            rotation: Quat::from_rotation_y(90.0_f32.to_radians()),
-                            scale: Vec3::new(1.0, 1.0, 1.0),
-                        },
-                        GlobalTransform::identity(),
-                        ))
-                    .insert(LevelTag)
-                    .with_children(|parent| {
-                        parent.spawn_scene(door_scene.clone());
-                    });
-            }
         }
     }
-    game.map = map;
 }
 
-fn move_player(
-    mut commands: Commands,
-    keyboard_input: Res<Input<KeyCode>>,
-    mut game: ResMut<Game>,
-    mut transforms: Query<&mut Transform>,
-    time: Res<Time>,
-) {
-    if !game.player.move_cooldown.tick(time.delta()).finished() { return; }
+// the level as its first step finds it
+fn remember_floor_start(mut floor_start: ResMut<FloorStart>, run: RunState) {
+    if floor_start.0.is_none() {
+        floor_start.0 = Some(run.snapshot());
+    }
+}
 
-    let mut moved = false;
-    let mut rotation = 0.0;
+fn generated_level(width: usize, length: usize, rng: &mut LevelRng) -> LevelFile {
+    let map = levelgen::generate_level_with(width, length, 7, 3, rng);
+    let spawn = levelgen::spawn_point(width, length);
+    LevelFile {
+        width,
+        length,
+        spawn,
+        patrols: Vec::new(),
+        bonuses: Vec::new(),
+        exit: Some(levelgen::exit_point(&map, spawn)),
+        cells: map.into_cells(),
+    }
+}
 
-    let player_pos = game.player.j * MAP_SIZE_I + game.player.i;
+// Floors get bigger on the way up
+fn floor_size(config: &LevelConfig, floor: u32) -> (usize, usize) {
+    let growth = FLOOR_GROWTH * floor.saturating_sub(1) as usize;
+    (
+        (config.width + growth).min(MAX_FLOOR_WIDTH.max(config.width)),
+        (config.length + growth).min(MAX_FLOOR_LENGTH.max(config.length)),
+    )
+}
 
-    if keyboard_input.pressed(KeyCode::Up) {
-        if game.player.i < MAP_SIZE_I - 1 && game.map[player_pos].open_sides[1] {
-            game.player.i += 1;
-        }
-        rotation = -std::f32::consts::FRAC_PI_2;
-        moved = true;
+// point in the middle of the level, at floor height
+fn map_center(map: &Grid) -> Vec3 {
+    Vec3::new(map.width() as f32 / 2.0, 0.0, map.length() as f32 / 2.0)
+}
+
+// write the current level, with the routes and intel seen so far, to a file designers can tweak
+fn export_level(keyboard_input: Res<Input<KeyCode>>, game: Res<Game>) {
+    if !keyboard_input.just_pressed(KeyCode::F2) {
+        return;
     }
-    if keyboard_input.pressed(KeyCode::Down) {
-        if game.player.i > 0 && game.map[player_pos].open_sides[3] {
-            game.player.i -= 1;
-        }
-        rotation = std::f32::consts::FRAC_PI_2;
-        moved = true;
+    let level = LevelFile {
+        width: game.map.width(),
+        length: game.map.length(),
+        spawn: game.spawn,
+        patrols: game.patrols.clone(),
+        bonuses: game.bonus_spots.clone(),
+        exit: Some(game.exit),
+        cells: game.map.cells().to_vec(),
+    };
+    let path = match game.floor {
+        1 => format!("assets/levels/seed-{}.ron", game.seed),
+        floor => format!("assets/levels/seed-{}-floor-{}.ron", game.seed, floor),
+    };
+    match levelfile::save_level(&path, &level) {
+        Ok(()) => info!("level saved to {}", path),
+        Err(err) => error!("failed to save {}: {}", path, err),
     }
-    if keyboard_input.pressed(KeyCode::Right) {
-        if game.player.j < MAP_SIZE_J - 1 && game.map[player_pos].open_sides[2] {
-            game.player.j += 1;
+}
+
+fn move_player(
+    input: Res<PlayerInput>,
+    mut game: ResMut<Game>,
+    mut turn: ResMut<Turn>,
+    mut noises: EventWriter<Noise>,
+) {
+    // a clicked route is walked one step at a time
+    if let Some(route) = &input.route {
+        game.player.route = route.clone();
+    }
+    if !game.player.move_cooldown.tick(sim::step_duration()).finished() { return; }
+    if !turn.player_can_act() { return; }
+
+    let mut facing: Option<Direction> = None;
+    let running = input.run;
+
+    for &dir in &input.steer {
+        // steering by hand drops the clicked route
+        game.player.route.clear();
+        // walking into a closed door opens it
+        if door::open_door(&mut game, &mut turn, &mut noises, dir) == DoorUse::NoDoor {
+            step_player(&mut game, &mut turn, &mut noises, dir, running);
         }
-        rotation = std::f32::consts::PI;
-        moved = true;
+        facing = Some(dir);
     }
-    if keyboard_input.pressed(KeyCode::Left) {
-        if game.player.j > 0 && game.map[player_pos].open_sides[0] {
-            game.player.j -= 1;
+
+    // otherwise keep walking the clicked route
+    if facing.is_none() {
+        if let Some(&next) = game.player.route.last() {
+            match Direction::between((game.player.i, game.player.j), next) {
+                Some(dir) => match door::open_door(&mut game, &mut turn, &mut noises, dir) {
+                    DoorUse::Opened => facing = Some(dir),
+                    DoorUse::Blocked => game.player.route.clear(),
+                    DoorUse::NoDoor if step_player(&mut game, &mut turn, &mut noises, dir, running) => {
+                        game.player.route.pop();
+                        facing = Some(dir);
+                    },
+                    // out of action points, carry on next turn
+                    DoorUse::Waiting => {},
+                    DoorUse::NoDoor if game.map.is_open(game.player.i, game.player.j, dir) => {},
+                    DoorUse::NoDoor => game.player.route.clear(),
+                },
+                None => game.player.route.clear(),
+            }
         }
-        rotation = 0.0;
-        moved = true;
     }
 
     // move on the board
-    if !moved { return; }
+    let facing = match facing {
+        Some(dir) => dir,
+        None => return,
+    };
 
     //println!("{}:{}", game.player.i, game.player.j);
-    //println!("{:?}", game.map[game.player.j * MAP_SIZE_I + game.player.i]);
+    game.player.facing = facing;
+    game.player.move_cooldown.set_duration(Duration::from_secs_f32(if running { RUN_DELAY } else { MOVE_DELAY }));
     game.player.move_cooldown.reset();
-    *transforms.get_mut(game.player.entity.unwrap()).unwrap() = Transform {
-        translation: Vec3::new( game.player.i as f32, game.map[game.player.j * MAP_SIZE_I + game.player.i].height, game.player.j as f32),
-        rotation: Quat::from_rotation_y(rotation),
-        ..Default::default()
+
+    if game.bonus() == Some((game.player.i, game.player.j)) {
+        game.score += 1;
+        game.floor_intel += 1;
+        game.bonus.active = false;
+        game.bonus.collected_on = turn.number;
+    }
+
+    // nothing left to spend, the guards move
+    if turn.mode == PlayMode::TurnBased && turn.action_points == 0 {
+        turn.end_player_turn();
+    }
+}
+
+// Step the player one cell in `dir`, false when a wall or a shut door is in the way or the action points don't cover it.
+// Running halves the action points a step takes but can be heard.
+fn step_player(game: &mut Game, turn: &mut Turn, noises: &mut EventWriter<Noise>, dir: Direction, running: bool) -> bool {
+    if !game.map.is_open(game.player.i, game.player.j, dir) {
+        return false;
+    }
+    let (i, j) = match game.map.step(game.player.i, game.player.j, dir) {
+        Some(next) => next,
+        None => return false,
+    };
+    let (from, to) = match (game.map.get(game.player.i, game.player.j), game.map.get(i, j)) {
+        (Some(from), Some(to)) => (*from, *to),
+        _ => return false,
     };
+    let points = match TerrainCost::player().step_cost(&from, &to, dir) {
+        Some(cost) if running => (turn::action_points(cost) / 2).max(1),
+        Some(cost) => turn::action_points(cost),
+        None => return false,
+    };
+    if !turn.spend(points) {
+        return false;
+    }
+    game.player.i = i;
+    game.player.j = j;
+    if running {
+        noises.send(Noise { at: (i, j), radius: noise::RUN_NOISE });
+    }
+    true
+}
 
-    if let Some(entity) = game.bonus.entity {
-        if game.player.i == game.bonus.i && game.player.j == game.bonus.j {
-            game.score += 1;
-            commands.entity(entity).despawn_recursive();
-            game.bonus.entity = None;
-        }
+// the player's model stands on their cell, facing their way
+fn place_player(game: Res<Game>, view: Res<LevelView>, mut transforms: Query<&mut Transform>) {
+    if !game.is_changed() {
+        return;
+    }
+    let height = game.map.get(game.player.i, game.player.j).map_or(0.0, |cell| cell.height);
+    if let Some(mut transform) = view.player.and_then(|entity| transforms.get_mut(entity).ok()) {
+        *transform = Transform {
+            translation: Vec3::new(game.player.i as f32, height, game.player.j as f32),
+            rotation: Quat::from_rotation_y(game.player.facing.yaw()),
+            ..Default::default()
+        };
     }
 }
 
 // change the focus of the camera
 fn focus_camera(
     time: Res<Time>,
-    mut game: ResMut<Game>,
+    game: Res<Game>,
+    mut view: ResMut<LevelView>,
     mut transforms: QuerySet<( QueryState<&mut Transform, With<Camera>>, QueryState<&Transform>,)>,
 ) {
     const SPEED: f32 = 2.0;
     // if there is both a player and a bonus, target the mid-point of them
     // otherwise, if there is only a player, target the player
-    if let Some(player_entity) = game.player.entity {
+    if let Some(player_entity) = view.player {
         if let Ok(player_transform) = transforms.q1().get(player_entity) {
-            game.camera_should_focus = player_transform.translation;
+            view.camera_should_focus = player_transform.translation;
         }
     // otherwise, target the middle
     } else {
-        game.camera_should_focus = Vec3::from(RESET_POS);
+        view.camera_should_focus = map_center(&game.map);
     }
     // calculate the camera motion based on the difference between where the camera is looking
     // and where it should be looking; the greater the distance, the faster the motion;
     // smooth out the camera movement using the frame time
-    let mut camera_motion = game.camera_should_focus - game.camera_is_focus;
+    let mut camera_motion = view.camera_should_focus - view.camera_is_focus;
     if camera_motion.length() > 0.2 {
         camera_motion *= SPEED * time.delta_seconds();
         // set the new camera's actual focus
-        game.camera_is_focus += camera_motion;
+        view.camera_is_focus += camera_motion;
     }
     // look at that new camera's actual focus
     for mut transform in transforms.q0().iter_mut() {
-        transform.translation = game.camera_is_focus + Vec3::from_slice(&CAMERA_OFFSET);
+        transform.translation = view.camera_is_focus + Vec3::from_slice(&CAMERA_OFFSET);
     }
 }
 
 fn spawn_bonus(
-    mut state: ResMut<State<GameState>>,
-    mut commands: Commands,
     mut game: ResMut<Game>,
+    mut rng: ResMut<GameRng>,
+    turn: Res<Turn>,
 ) {
-    if *state.current() != GameState::Game || game.bonus.entity.is_some() {
+    if game.bonus.active {
         return;
     }
-    loop {
-        game.bonus.i = rand::thread_rng().gen_range(0..MAP_SIZE_I);
-        game.bonus.j = rand::thread_rng().gen_range(0..MAP_SIZE_J);
-        if game.bonus.i != game.player.i || game.bonus.j != game.player.j {
-            break;
+    let due = match turn.mode {
+        PlayMode::RealTime => game.bonus.respawn.tick(sim::step_duration()).just_finished(),
+        PlayMode::TurnBased => turn.number > game.bonus.collected_on,
+    };
+    if !due {
+        return;
+    }
+    if let Some(&(i, j)) = game.bonus_spots.get(game.bonus.spawned) {
+        game.bonus.i = i;
+        game.bonus.j = j;
+    } else {
+        // somewhere the player can reach without a keycard, but not right next to them
+        let distances = pathfinding::Pathfinder::new(&TerrainCost::player()).distance_map(&game.map, &[(game.player.i, game.player.j)]);
+        let exit = game.exit;
+        let mut spots: Vec<(usize, usize)> = distances.reachable()
+            .filter(|&(spot, cost)| cost >= BONUS_MIN_STEPS * pathfinding::DIST_BETWEEN && spot != exit)
+            .map(|(spot, _)| spot)
+            .collect();
+        if spots.is_empty() {
+            spots = distances.reachable().filter(|&(spot, cost)| cost > 0 && spot != exit).map(|(spot, _)| spot).collect();
         }
+        if spots.is_empty() {
+            return;
+        }
+        let spot = spots[rng.0.gen_range(0..spots.len())];
+        game.bonus.i = spot.0;
+        game.bonus.j = spot.1;
+        game.bonus_spots.push(spot);
     }
-    game.bonus.entity = Some(
-        commands
-            .spawn_bundle((
-                Transform {
-                    translation: Vec3::new(
-                        game.bonus.i as f32,
-                        game.map[game.bonus.j * MAP_SIZE_I + game.bonus.i].height,
-                        game.bonus.j as f32,
-                    ),
+    game.bonus.spawned += 1;
+    game.bonus.discovered = false;
+    game.bonus.active = true;
+}
+
+// intel stays hidden in the fog until the player has seen its cell
+fn discover_bonus(mut game: ResMut<Game>, fog: Res<FogOfWar>) {
+    if game.bonus.active && !game.bonus.discovered && fog.is_visible(game.bonus.i, game.bonus.j) {
+        game.bonus.discovered = true;
+    }
+}
+
+// Put the intel's model down when some is spawned and take it away once picked up
+fn draw_bonus(mut commands: Commands, mut view: ResMut<LevelView>, game: Res<Game>) {
+    let wanted = game.bonus().map(|_| game.bonus.spawned);
+    if view.bonus.map(|(_, spawned)| spawned) == wanted {
+        return;
+    }
+    if let Some((entity, _)) = view.bonus.take() {
+        commands.entity(entity).despawn_recursive();
+    }
+    view.bonus_light = None;
+    if let Some(spawned) = wanted {
+        let entity = spawn_bonus_model(&mut commands, &mut view, &game);
+        view.bonus = Some((entity, spawned));
+    }
+}
+
+// Spawn the intel and its light on its cell, the light stays off until the player has seen it
+fn spawn_bonus_model(commands: &mut Commands, view: &mut LevelView, game: &Game) -> Entity {
+    let intensity = if game.bonus.discovered { BONUS_LIGHT } else { 0.0 };
+    let mut light = None;
+    let entity = commands
+        .spawn_bundle((
+            Transform {
+                translation: Vec3::new(
+                    game.bonus.i as f32,
+                    game.map.get(game.bonus.i, game.bonus.j).map_or(0.0, |cell| cell.height),
+                    game.bonus.j as f32,
+                ),
+                ..Default::default()
+            },
+            GlobalTransform::identity(),
+        ))
+        .insert(LevelTag)
+        .with_children(|children| {
+            light = Some(children.spawn_bundle(PointLightBundle {
+                point_light: PointLight {
+                    color: Color::rgb(1.0, 1.0, 0.0),
+                    intensity,
+                    range: 10.0,
                     ..Default::default()
                 },
-                GlobalTransform::identity(),
-            ))
-            .insert(LevelTag)
-            .with_children(|children| {
-                children.spawn_bundle(PointLightBundle {
-                    point_light: PointLight {
-                        color: Color::rgb(1.0, 1.0, 0.0),
-                        intensity: 5.0,
-                        range: 10.0,
-                        ..Default::default()
-                    },
-                    transform: Transform::from_xyz(0.0, 0.2, 0.0),
-                    ..Default::default()
-                });
-                children.spawn_scene(game.bonus.handle.clone());
-            })
-            .id(),
-    );
+                transform: Transform::from_xyz(0.0, 0.2, 0.0),
+                ..Default::default()
+            }).id());
+            children.spawn_scene(view.bonus_scene.clone());
+        })
+        .id();
+    view.bonus_light = light;
+    entity
 }
 
-// let the case turn on itself
-fn rotate_bonus(game: Res<Game>, time: Res<Time>, mut transforms: Query<&mut Transform>) {
-    if let Some(entity) = game.bonus.entity {
+// let the case turn on itself, and light up once discovered
+fn rotate_bonus(
+    game: Res<Game>,
+    view: Res<LevelView>,
+    time: Res<Time>,
+    mut transforms: Query<&mut Transform>,
+    mut lights: Query<&mut PointLight>,
+) {
+    if let Some((entity, _)) = view.bonus {
+        if let Some(mut light) = view.bonus_light.and_then(|light| lights.get_mut(light).ok()) {
+            if game.bonus.discovered && light.intensity != BONUS_LIGHT {
+                light.intensity = BONUS_LIGHT;
+            }
+        }
         if let Ok(mut transform) = transforms.get_mut(entity) {
             transform.rotate(Quat::from_rotation_y(time.delta_seconds()));
-            transform.scale = Vec3::splat( 1.0 + (game.score as f32 / 10.0 * time.seconds_since_startup().sin() as f32).abs(),);
+            transform.scale = if game.bonus.discovered {
+                Vec3::splat( 1.0 + (game.score as f32 / 10.0 * time.seconds_since_startup().sin() as f32).abs(),)
+            } else {
+                Vec3::ZERO
+            };
         }
     }
 }
 
+// stepping on the elevator with enough intel takes the player one floor up
+fn extract(mut game: ResMut<Game>, mut state: ResMut<State<GameState>>, mut clock: ResMut<SimClock>) {
+    if game.advancing || !game.can_extract() || (game.player.i, game.player.j) != game.exit {
+        return;
+    }
+    if state.set(GameState::NextFloor).is_ok() {
+        game.advancing = true;
+        clock.halted = true;
+    }
+}
+
+// light up the exit once it can be taken
+fn draw_exit(game: Res<Game>, view: Res<LevelView>, mut materials: Query<&mut Handle<StandardMaterial>>) {
+    let wanted = if game.can_extract() { &view.open } else { &view.locked };
+    if let Some(mut material) = view.exit.and_then(|entity| materials.get_mut(entity).ok()) {
+        if *material != *wanted {
+            *material = wanted.clone();
+        }
+    }
+}
+
+// the last floor is gone, build the next one
+fn next_floor(mut state: ResMut<State<GameState>>) {
+    let _ = state.set(GameState::Game);
+}
+
 // update the score displayed during the game
-fn scoreboard_system(game: Res<Game>, mut query: Query<&mut Text>) {
+fn scoreboard_system(game: Res<Game>, turn: Res<Turn>, preview: Res<RoutePreview>, mut query: Query<&mut Text>) {
     let mut text = query.single_mut();
-    text.sections[0].value = format!("Intel collected: {}", game.score);
+    let objective = if game.can_extract() {
+        "Exit open, get to the elevator".to_string()
+    } else {
+        format!("Intel to extract: {}/{}", game.floor_intel, game.intel_required())
+    };
+    let mut score = format!("Floor {} - Intel collected: {}\n{}", game.floor, game.score, objective);
+    score += &match (turn.mode, turn.phase) {
+        (PlayMode::RealTime, _) => String::new(),
+        (PlayMode::TurnBased, TurnPhase::Player) => format!(
+            "\nTurn {} - AP {}/{} (Enter ends turn)",
+            turn.number, turn.action_points, turn::PLAYER_ACTION_POINTS,
+        ),
+        (PlayMode::TurnBased, TurnPhase::Enemy) => format!("\nTurn {} - guards moving", turn.number),
+    };
+    if game.player.keycard {
+        score += "\nKeycard";
+    }
+    // cost of the route under the cursor
+    if !preview.path.is_empty() {
+        score += &match turn.mode {
+            PlayMode::RealTime => format!("\nRoute: {} steps, cost {}", preview.path.len(), preview.cost),
+            PlayMode::TurnBased => format!("\nRoute: {} steps, cost {} ({} AP)", preview.path.len(), preview.cost, preview.action_points),
+        };
+    }
+    text.sections[0].value = score;
 }
 
-// restart the game when pressing spacebar
-fn gameover_keyboard(mut state: ResMut<State<GameState>>, keyboard_input: Res<Input<KeyCode>>) {
-    if keyboard_input.just_pressed(KeyCode::Space) {
-        state.set(GameState::Game).unwrap();
-    }
+// Run that just ended, the player names it when it makes the leaderboard
+#[derive(Default)]
+struct GameOverScreen {
+    entry: Option<ScoreEntry>,
+    naming: bool,
+    // Place on the board once recorded
+    rank: Option<usize>,
 }
-fn display_score(mut commands: Commands, asset_server: Res<AssetServer>, game: Res<Game>) {
-    let path = "highscore.txt";
-    let mut highscore: i32 = 0;
-    if std::path::Path::new(path).exists() {
-        let mut input = File::open(path).expect("Unable to open highscore");
-        let buffered = BufReader::new(input);
-        for line in buffered.lines() {
-            highscore = line.expect("Failed to read").trim().parse().expect("Failed to parse")
+
+#[derive(Component)]
+struct GameOverText;
+
+// type a name for the leaderboard, `Enter` records it
+fn name_entry(
+    mut screen: ResMut<GameOverScreen>,
+    mut leaderboard: ResMut<Leaderboard>,
+    mut characters: EventReader<ReceivedCharacter>,
+    keyboard_input: Res<Input<KeyCode>>,
+) {
+    if !screen.naming {
+        return;
+    }
+    let screen = &mut *screen;
+    let entry = match &mut screen.entry {
+        Some(entry) => entry,
+        None => return,
+    };
+    for typed in characters.iter() {
+        if (typed.char.is_alphanumeric() || typed.char == '-' || typed.char == '_') && entry.name.chars().count() < MAX_NAME_LENGTH {
+            entry.name.push(typed.char);
         }
     }
-    highscore = if highscore < game.score { game.score } else { highscore };
-    let mut output = File::create(path).expect("Unable to create highscore");
-    output.write(highscore.to_string().as_bytes());
+    if keyboard_input.just_pressed(KeyCode::Back) {
+        entry.name.pop();
+    }
+    if keyboard_input.just_pressed(KeyCode::Return) && !entry.name.is_empty() {
+        leaderboard.last_name = entry.name.clone();
+        screen.rank = leaderboard.insert(entry.clone());
+        screen.naming = false;
+        if let Err(err) = leaderboard.save(Leaderboard::path()) {
+            error!("failed to save {}: {}", Leaderboard::path().display(), err);
+        }
+    }
+}
+
+// restart the game when pressing spacebar, once the score is recorded
+fn gameover_keyboard(mut state: ResMut<State<GameState>>, screen: Res<GameOverScreen>, keyboard_input: Res<Input<KeyCode>>) {
+    if keyboard_input.just_pressed(KeyCode::Space) && !screen.naming {
+        state.set(GameState::Game).unwrap();
+    }
+}
+
+fn display_score(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    game: Res<Game>,
+    leaderboard: Res<Leaderboard>,
+    playback: Res<Playback>,
+    mut screen: ResMut<GameOverScreen>,
+) {
+    let entry = ScoreEntry {
+        name: leaderboard.last_name.clone(),
+        score: game.score,
+        date: leaderboard::now(),
+        floor: game.floor,
+        seed: game.seed,
+        difficulty: game.difficulty,
+    };
+    *screen = GameOverScreen {
+        // a replay doesn't make it on the board again
+        naming: !playback.is_active() && leaderboard.qualifies(entry.score, entry.floor),
+        entry: Some(entry),
+        rank: None,
+    };
+    let font = asset_server.load(fonts::MAIN_FONT);
     commands
         .spawn_bundle(NodeBundle {
             style: Style {
@@ -490,16 +1153,51 @@ fn display_score(mut commands: Commands, asset_server: Res<AssetServer>, game: R
         .insert(GameOverTag)
         .with_children(|parent| {
             parent.spawn_bundle(TextBundle {
-                text: Text::with_section(
-                    format!("Intel Collected: {}\nHigh Score: {}", game.score, highscore),
-                    TextStyle {
-                        font: asset_server.load(fonts::MAIN_FONT),
-                        font_size: 80.0,
-                        color: Color::rgb(0.5, 0.5, 1.0),
-                    },
-                    Default::default(),
-                ),
+                text: Text {
+                    sections: vec![
+                        TextSection {
+                            value: String::new(),
+                            style: TextStyle {
+                                font: font.clone(),
+                                font_size: 60.0,
+                                color: Color::rgb(0.5, 0.5, 1.0),
+                            },
+                        },
+                        TextSection {
+                            value: String::new(),
+                            style: TextStyle {
+                                font: font.clone(),
+                                font_size: 24.0,
+                                color: Color::rgb(0.8, 0.8, 0.9),
+                            },
+                        },
+                    ],
+                    ..Default::default()
+                },
                 ..Default::default()
-            });
+            })
+            .insert(GameOverText);
         });
 }
+
+// the run's results, the name being typed and the leaderboard
+fn draw_gameover(
+    screen: Res<GameOverScreen>,
+    leaderboard: Res<Leaderboard>,
+    game: Res<Game>,
+    mut query: Query<&mut Text, With<GameOverText>>,
+) {
+    if !screen.is_changed() && !leaderboard.is_changed() {
+        return;
+    }
+    let mut text = match query.get_single_mut() {
+        Ok(text) => text,
+        Err(_) => return,
+    };
+    let prompt = match &screen.entry {
+        Some(entry) if screen.naming => format!("New high score! Name: {}_\n(Enter to save)", entry.name),
+        _ => "Press Space to play again".to_string(),
+    };
+    text.sections[0].value = format!("Intel Collected: {}\nFloor Reached: {}\nSeed: {}\n{}\n\n", game.score, game.floor, game.seed, prompt);
+    text.sections[1].value = leaderboard.table(screen.rank);
+}
@@ -0,0 +1,218 @@
+use std::ops::Deref;
+use serde::{Serialize, Deserialize};
+use crate::{Cell, DoorState};
+
+// Sides of a cell, in the order of `Cell::open_sides`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Direction {
+    // j - 1
+    North,
+    // i + 1
+    East,
+    // j + 1
+    South,
+    // i - 1
+    West,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 4] = [Direction::North, Direction::East, Direction::South, Direction::West];
+
+    pub fn from_index(index: usize) -> Self {
+        Self::ALL[index % 4]
+    }
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    pub fn opposite(self) -> Self {
+        Self::from_index(self.index() + 2)
+    }
+
+    pub fn offset(self) -> (i32, i32) {
+        match self {
+            Direction::North => (0, -1),
+            Direction::East => (1, 0),
+            Direction::South => (0, 1),
+            Direction::West => (-1, 0),
+        }
+    }
+
+    // Rotation around Y that makes a model face this way
+    pub fn yaw(self) -> f32 {
+        match self {
+            Direction::North => 0.0,
+            Direction::East => -std::f32::consts::FRAC_PI_2,
+            Direction::South => std::f32::consts::PI,
+            Direction::West => std::f32::consts::FRAC_PI_2,
+        }
+    }
+
+    // Direction of a single orthogonal step between two cells
+    pub fn between(from: (usize, usize), to: (usize, usize)) -> Option<Self> {
+        let offset = (to.0 as i32 - from.0 as i32, to.1 as i32 - from.1 as i32);
+        Self::ALL.iter().copied().find(|dir| dir.offset() == offset)
+    }
+}
+
+impl Default for Direction {
+    fn default() -> Self {
+        Direction::North
+    }
+}
+
+// The cells of a level with their dimensions. All coordinate math goes through here
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Grid {
+    width: usize,
+    length: usize,
+    cells: Vec<Cell>,
+    // Bumped whenever a door changes, so views of the grid know to look again
+    #[serde(skip)]
+    door_revision: u32,
+}
+
+impl Grid {
+    // `cells` are laid out row by row, `j * width + i`.
+    // A door belongs to both cells of its edge, whichever side it was set on
+    pub fn new(width: usize, length: usize, cells: Vec<Cell>) -> Self {
+        assert_eq!(cells.len(), width * length, "a {}x{} grid needs {} cells", width, length, width * length);
+        let mut grid = Self { width, length, cells, door_revision: 0 };
+        for index in 0..grid.cells.len() {
+            let cell = grid.cells[index];
+            for dir in Direction::ALL {
+                if let (Some(state), Some((x, y))) = (cell.door(dir), grid.step(cell.i, cell.j, dir)) {
+                    let other = &mut grid.cells[y * width + x];
+                    if !other.doors[dir.opposite().index()] {
+                        other.doors[dir.opposite().index()] = true;
+                        other.door_states[dir.opposite().index()] = state;
+                    }
+                }
+            }
+        }
+        grid
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    pub fn cells(&self) -> &[Cell] {
+        &self.cells
+    }
+
+    pub fn into_cells(self) -> Vec<Cell> {
+        self.cells
+    }
+
+    pub fn in_bounds(&self, i: i32, j: i32) -> bool {
+        i >= 0 && j >= 0 && (i as usize) < self.width && (j as usize) < self.length
+    }
+
+    // Position in `cells`, `None` when outside the grid
+    pub fn index(&self, i: usize, j: usize) -> Option<usize> {
+        if i < self.width && j < self.length {
+            Some(j * self.width + i)
+        } else {
+            None
+        }
+    }
+
+    pub fn get(&self, i: usize, j: usize) -> Option<&Cell> {
+        self.index(i, j).map(|index| &self.cells[index])
+    }
+
+    pub fn get_mut(&mut self, i: usize, j: usize) -> Option<&mut Cell> {
+        self.index(i, j).map(move |index| &mut self.cells[index])
+    }
+
+    // Neighbouring position in `dir`, walls ignored
+    pub fn step(&self, i: usize, j: usize, dir: Direction) -> Option<(usize, usize)> {
+        let (di, dj) = dir.offset();
+        let (x, y) = (i as i32 + di, j as i32 + dj);
+        if self.in_bounds(x, y) {
+            Some((x as usize, y as usize))
+        } else {
+            None
+        }
+    }
+
+    // Whether the edge of (i, j) in `dir` can be crossed right now, closed doors block it
+    pub fn is_open(&self, i: usize, j: usize, dir: Direction) -> bool {
+        self.is_passage(i, j, dir) && self.door(i, j, dir).map_or(true, |state| state == DoorState::Open)
+    }
+
+    // Whether the edge of (i, j) in `dir` has no wall, doors in it may still be shut.
+    // Both cells must have the side open, so every passage can be walked both ways
+    pub fn is_passage(&self, i: usize, j: usize, dir: Direction) -> bool {
+        match (self.get(i, j), self.step(i, j, dir).and_then(|(x, y)| self.get(x, y))) {
+            (Some(cell), Some(next)) => cell.open_sides[dir.index()] && next.open_sides[dir.opposite().index()],
+            _ => false,
+        }
+    }
+
+    // Cells reachable in one step from (i, j)
+    pub fn neighbors(&self, i: usize, j: usize) -> impl Iterator<Item = (Direction, &Cell)> + '_ {
+        self.passages(i, j).filter(move |&(dir, _)| self.is_open(i, j, dir))
+    }
+
+    // Cells next to (i, j) without a wall in between, whether or not a door is shut
+    pub fn passages(&self, i: usize, j: usize) -> impl Iterator<Item = (Direction, &Cell)> + '_ {
+        Direction::ALL.iter().filter_map(move |&dir| {
+            if !self.is_passage(i, j, dir) {
+                return None;
+            }
+            let (x, y) = self.step(i, j, dir)?;
+            self.get(x, y).map(|cell| (dir, cell))
+        })
+    }
+
+    // State of the door on the edge of (i, j) in `dir`, `None` when there is none
+    pub fn door(&self, i: usize, j: usize, dir: Direction) -> Option<DoorState> {
+        self.get(i, j).and_then(|cell| cell.door(dir))
+    }
+
+    // Change a door on both sides of its edge, false when there is no door there
+    pub fn set_door(&mut self, i: usize, j: usize, dir: Direction, state: DoorState) -> bool {
+        let (x, y) = match (self.door(i, j, dir), self.step(i, j, dir)) {
+            (Some(_), Some(next)) => next,
+            _ => return false,
+        };
+        self.cells[j * self.width + i].door_states[dir.index()] = state;
+        self.cells[y * self.width + x].door_states[dir.opposite().index()] = state;
+        self.door_revision += 1;
+        true
+    }
+
+    // Every door once, as the cell west or north of it and the side it is on
+    pub fn door_edges(&self) -> Vec<(usize, usize, Direction)> {
+        self.cells.iter()
+            .flat_map(|cell| [Direction::East, Direction::South].into_iter().map(move |dir| (cell.i, cell.j, dir)))
+            .filter(|&(i, j, dir)| self.door(i, j, dir).is_some() && self.step(i, j, dir).is_some())
+            .collect()
+    }
+
+    pub fn door_revision(&self) -> u32 {
+        self.door_revision
+    }
+}
+
+// Same cells, however many times their doors were opened and closed on the way
+impl PartialEq for Grid {
+    fn eq(&self, other: &Self) -> bool {
+        self.width == other.width && self.length == other.length && self.cells == other.cells
+    }
+}
+
+impl Deref for Grid {
+    type Target = [Cell];
+
+    fn deref(&self) -> &[Cell] {
+        &self.cells
+    }
+}
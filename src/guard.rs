@@ -1,24 +1,107 @@
 use bevy::{prelude::*, core::FixedTimestep};
-use crate::{GameState, Cell, game::Game, consts::{fonts, assets, MAP_SIZE_I, MAP_SIZE_J}, despawn_entities, pathfinding};
+use serde::{Serialize, Deserialize};
+use crate::{GameState, Cell, Terrain, DoorState, grid::{Grid, Direction}, game::{Game, GameRng, GameSystem, LevelTag}, turn::{self, Turn, PlayMode}, consts::{fonts, assets}, despawn_entities, pathfinding::{Pathfinder, ReservationTable, TerrainCost, DIST_BETWEEN}, levelgen::LevelRng, vision::{self, VisionParams, WatcherView, DangerTiles}, difficulty::{Difficulty, DifficultyProfile, GuardTier}, noise::{self, Noise}, fog::{FogOfWar, PLAYER_VISION, FogSystem}, save::Restore, sim::{self, SimStage, SimSystem, SimClock, PlayerInput}};
 use rand::Rng;
 
 #[derive(Default)]
-struct GuardRoster {
+pub struct GuardRoster {
     guards: Vec<Guard>,
-    handle: Handle<Scene>,
+    profile: DifficultyProfile,
+    // Patrol ticks so far, guards plan their legs against each other's reservations in this time frame
+    tick: u32,
+    reservations: ReservationTable,
+    next_id: usize,
+    // Guards the player can currently see, by id
+    in_view: Vec<usize>,
+    // Guards taken out of the level, they still count towards how many the score calls for
+    knocked_out: usize,
 }
 
-const VISION: usize = 3;
+// Plain copy of a guard, for saved runs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GuardSnapshot {
+    pub id: usize,
+    pub i: usize,
+    pub j: usize,
+    pub rotation: Direction,
+    pub current_path: Option<Vec<Cell>>,
+    pub pp: usize,
+    pub patrol_points: Vec<(usize, usize)>,
+    pub state: GuardState,
+    pub vision: VisionParams,
+    pub model: usize,
+}
+
+// Plain copy of the roster, for saved runs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RosterSnapshot {
+    pub guards: Vec<GuardSnapshot>,
+    pub tick: u32,
+    pub reservations: ReservationTable,
+    pub next_id: usize,
+    pub in_view: Vec<usize>,
+    pub knocked_out: usize,
+    pub profile: DifficultyProfile,
+}
+
+// Guards see a 90 degree cone
+pub const GUARD_VISION: VisionParams = VisionParams { range: 4.0, angle: 90.0 };
+// Guard steps spent staring at a glimpse before going to have a look
+const SUSPICION_STEPS: u32 = 2;
+// Guard steps an investigation lasts before the guard calms down and resumes its patrol
+const CALM_DOWN_STEPS: u32 = 12;
+// Cells searched around the last known position, and how far from it
+const SEARCH_CELLS: u32 = 3;
+const SEARCH_RADIUS: i32 = 3;
+// Action points it takes to knock out a guard from behind
+const TAKEDOWN_ACTION_POINTS: u32 = 2;
+// Guard steps a hunter keeps chasing after losing sight of the player
+const HUNT_LOST_STEPS: u32 = 3;
 
-#[derive(Default)]
 struct Guard {
-    entity: Option<Entity>,
+    id: usize,
     i: usize,
     j: usize,
-    rotation: usize,
+    rotation: Direction,
     current_path: Option<Vec<Cell>>,
     pp: usize,
     patrol_points: Vec<(usize, usize)>,
+    state: GuardState,
+    // Comes with the guard's tier, like its model
+    vision: VisionParams,
+    model: usize,
+}
+
+// What a guard is up to. Timers count guard steps so real time and turns behave the same
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GuardState {
+    Patrol,
+    // Caught a glimpse at `at`, stands and looks that way
+    Suspicious { at: (usize, usize), wait: u32 },
+    // Walks to `target`, then searches a few cells `around` the last known position
+    Investigating { around: (usize, usize), target: (usize, usize), searches_left: u32, calm: u32 },
+    // Fully spotted the player and goes after them
+    Hunting { last_seen: (usize, usize), lost: u32 },
+}
+
+impl Default for GuardState {
+    fn default() -> Self {
+        GuardState::Patrol
+    }
+}
+
+impl GuardState {
+    fn investigate(at: (usize, usize)) -> Self {
+        GuardState::Investigating { around: at, target: at, searches_left: SEARCH_CELLS, calm: CALM_DOWN_STEPS }
+    }
+}
+
+// How well a guard sees the player
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Sighting {
+    // At the edge of vision or hidden in a shadow
+    Glimpse,
+    Spotted,
 }
 
 pub struct GuardPlugin;
@@ -27,199 +110,635 @@ impl Plugin for GuardPlugin {
         app
             .init_resource::<GuardRoster>()
             .add_system_set(
-                SystemSet::new()
-                    .with_run_criteria(FixedTimestep::step(1.0))
-                    .with_system(patrol)
+                SystemSet::on_enter(GameState::Game)
+                    .with_system(setup_guards.after(GameSystem::SetupLevel))
             )
+            .add_system_set_to_stage(
+                SimStage,
+                SystemSet::new()
+                    .with_system(takedown.label(SimSystem::Player).label(SimSystem::Takedown).after(SimSystem::Door))
+                    .with_system(hear_noise.after(SimSystem::Level))
+                    .with_system(spawn_guard.after(hear_noise))
+                    .with_system(patrol.label(SimSystem::Guards).after(spawn_guard))
+                    .with_system(lookout.after(patrol))
+                    .with_system(interrupt_walk.after(lookout))
+                    .with_system(remember_guards.after(interrupt_walk).after(FogSystem::Reveal))
+            );
+    }
+}
+
+// Draws the guards and what they see
+pub struct GuardViewPlugin;
+impl Plugin for GuardViewPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<GuardModels>()
+            .init_resource::<DangerOverlay>()
             .add_system_set(
                 SystemSet::on_enter(GameState::Game)
-                    .with_system(setup_guards)
+                    .with_system(load_guard_models)
+                    .with_system(setup_danger)
             )
             .add_system_set(
                 SystemSet::on_update(GameState::Game)
-                    .with_system(spawn_guard)
-                    .with_system(lookout)
+                    .with_system(draw_guards)
+                    .with_system(toggle_danger)
+                    .with_system(show_danger.after(toggle_danger))
             )
             .add_system_set(
                 SystemSet::on_exit(GameState::GameOver)
                     .with_system(despawn_entities::<GuardTag>)
             )
+            .add_system_set(
+                SystemSet::on_enter(GameState::NextFloor)
+                    .with_system(despawn_entities::<GuardTag>)
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::Menu)
+                    .with_system(despawn_entities::<GuardTag>)
+            )
         ;
     }
 }
 
+impl GuardRoster {
+    pub fn snapshot(&self) -> RosterSnapshot {
+        RosterSnapshot {
+            guards: self.guards.iter().map(|guard| GuardSnapshot {
+                id: guard.id,
+                i: guard.i,
+                j: guard.j,
+                rotation: guard.rotation,
+                current_path: guard.current_path.clone(),
+                pp: guard.pp,
+                patrol_points: guard.patrol_points.clone(),
+                state: guard.state,
+                vision: guard.vision,
+                model: guard.model,
+            }).collect(),
+            tick: self.tick,
+            reservations: self.reservations.clone(),
+            next_id: self.next_id,
+            in_view: self.in_view.clone(),
+            knocked_out: self.knocked_out,
+            profile: self.profile.clone(),
+        }
+    }
+
+    // Take the saved guards back, their models follow by themselves
+    fn restore(&mut self, saved: RosterSnapshot) {
+        self.guards = saved.guards.into_iter().map(|guard| Guard {
+            id: guard.id,
+            i: guard.i,
+            j: guard.j,
+            rotation: guard.rotation,
+            current_path: guard.current_path,
+            pp: guard.pp,
+            patrol_points: guard.patrol_points,
+            state: guard.state,
+            vision: guard.vision,
+            model: guard.model,
+        }).collect();
+        self.tick = saved.tick;
+        self.reservations = saved.reservations;
+        self.next_id = saved.next_id;
+        self.in_view = saved.in_view;
+        self.knocked_out = saved.knocked_out;
+        self.profile = saved.profile;
+    }
+
+    // Where the guards the player can see stand and walk next, with how far each sees. Only they give
+    // their view away, and a turn-based guard walks a few steps before the player moves again
+    pub fn danger_views(&self, fog: &FogOfWar, mode: PlayMode) -> Vec<(WatcherView, VisionParams)> {
+        let steps = match mode {
+            PlayMode::RealTime => 1,
+            PlayMode::TurnBased => turn::GUARD_STEPS_PER_TURN as usize,
+        };
+        self.guards.iter().filter(|guard| fog.is_visible(guard.i, guard.j)).map(|guard| (WatcherView {
+            at: (guard.i, guard.j),
+            facing: guard.rotation,
+            ahead: guard.current_path.iter().flat_map(|path| path.iter().rev()).take(steps).map(|cell| (cell.i, cell.j)).collect(),
+        }, guard.vision)).collect()
+    }
+}
+
 fn setup_guards(
-    mut commands: Commands,
     mut guards: ResMut<GuardRoster>,
-    asset_server: Res<AssetServer>,
+    mut restore: ResMut<Restore>,
+    game: Res<Game>,
 ) {
     guards.guards = Vec::new();
-    guards.handle = asset_server.load(assets::ASTRONAUTS[0]);
+    guards.tick = 0;
+    guards.reservations = ReservationTable::default();
+    guards.in_view.clear();
+    guards.knocked_out = 0;
+    guards.profile = game.difficulty.profile();
+    // a saved run brings its guards back where they were
+    if let Some(saved) = restore.guards.take() {
+        guards.restore(saved);
+    }
 }
 
 #[derive(Component)]
 struct GuardTag;
 
+// Model of each guard tier
+#[derive(Default)]
+struct GuardModels(Vec<Handle<Scene>>);
+
+// Drawn guard, `id` in the roster
+#[derive(Component)]
+struct GuardModel {
+    id: usize,
+    light: Option<Entity>,
+}
+
+fn load_guard_models(mut models: ResMut<GuardModels>, asset_server: Res<AssetServer>) {
+    models.0 = assets::ASTRONAUTS.iter().map(|&model| asset_server.load(model)).collect();
+}
+
 fn patrol(
-    mut commands: Commands,
     mut game: ResMut<Game>,
     mut guards: ResMut<GuardRoster>,
-    mut transforms: Query<&mut Transform>,
-    mut state: ResMut<State<GameState>>,
+    mut rng: ResMut<GameRng>,
+    mut turn: ResMut<Turn>,
 ) {
-    if *state.current() != GameState::Game {
+    // once a second in real time, a few quick steps per enemy phase when turn-based
+    if !turn.guard_step_due(sim::step_duration()) {
         return;
     }
-    for guard in guards.guards.iter_mut() {
-        match &mut guard.current_path {
+    let roster = &mut *guards;
+    // Guards move into this tick
+    roster.tick += 1;
+    let tick = roster.tick;
+    roster.reservations.release_before(tick);
+    let mut occupied: Vec<(usize, usize)> = roster.guards.iter().map(|guard| (guard.i, guard.j)).collect();
+
+    for (index, guard) in roster.guards.iter_mut().enumerate() {
+        let goal = match next_goal(guard, &game, &mut rng.0) {
+            Some(goal) => goal,
             None => {
-                guard.pp = if guard.pp as i32 >= guard.patrol_points.len() as i32 - 1 { 0 } else { guard.pp + 1 };
-                guard.current_path = pathfinding::find_path(
-                    &game,
-                    &game.map[guard.j * MAP_SIZE_I + guard.i],
-                    &game.map[guard.patrol_points[guard.pp].1 * MAP_SIZE_I + guard.patrol_points[guard.pp].0],
-                );
+                // standing still, maybe turned around
+                guard.current_path = None;
+                roster.reservations.release(guard.id);
+                roster.reservations.reserve(guard.i, guard.j, tick, guard.id);
+                continue;
             },
-            Some(path) => {
-                let cell = path.pop().unwrap();
-                let mut rotation = 0.0;
-                if guard.i < cell.i {
-                    rotation = -std::f32::consts::FRAC_PI_2;
-                    guard.rotation = 1;
-                } else if guard.i > cell.i {
-                    rotation = std::f32::consts::FRAC_PI_2;
-                    guard.rotation = 3;
-                } else if guard.j < cell.j {
-                    rotation = std::f32::consts::PI;
-                    guard.rotation = 2;
-                } else if guard.j > cell.j {
-                    rotation = 0.0;
-                    guard.rotation = 0;
+        };
+        // plan again whenever the goal moved, paths are goal first
+        let planned = guard.current_path.as_ref().and_then(|path| path.first()).map(|cell| (cell.i, cell.j));
+        if planned != Some(goal) {
+            plan_leg(guard, goal, &game, &mut roster.reservations, tick - 1);
+        }
+        let cell = match guard.current_path.as_ref().and_then(|path| path.last()) {
+            Some(cell) => *cell,
+            None => continue,
+        };
+        let dir = Direction::between((guard.i, guard.j), (cell.i, cell.j));
+        if let Some(dir) = dir.filter(|&dir| game.map.door(guard.i, guard.j, dir).map_or(false, |state| state != DoorState::Open)) {
+            // opening the door takes the step, guards carry keys for the locked ones
+            game.map.set_door(guard.i, guard.j, dir, DoorState::Open);
+            guard.rotation = dir;
+            continue;
+        }
+        if occupied.iter().enumerate().any(|(other, &cell_taken)| other != index && cell_taken == (cell.i, cell.j)) {
+            // someone is in the way, stay put and route around them
+            plan_leg(guard, goal, &game, &mut roster.reservations, tick);
+            continue;
+        }
+        if let Some(path) = &mut guard.current_path {
+            path.pop();
+            if path.is_empty() {
+                guard.current_path = None;
+            }
+        }
+        if let Some(dir) = dir {
+            guard.rotation = dir;
+        }
+        guard.i = cell.i;
+        guard.j = cell.j;
+        occupied[index] = (cell.i, cell.j);
+    }
+}
+
+// Advance the guard's state by one step and return the cell it heads for, `None` to stay put
+fn next_goal(guard: &mut Guard, game: &Game, rng: &mut LevelRng) -> Option<(usize, usize)> {
+    let at = (guard.i, guard.j);
+    match guard.state {
+        GuardState::Patrol => {
+            if at == guard.patrol_points[guard.pp] {
+                guard.pp = (guard.pp + 1) % guard.patrol_points.len();
+            }
+            Some(guard.patrol_points[guard.pp])
+        },
+        GuardState::Suspicious { at: glimpse, wait } => {
+            if let Some(dir) = facing_towards(at, glimpse) {
+                guard.rotation = dir;
+            }
+            guard.state = if wait <= 1 {
+                GuardState::investigate(glimpse)
+            } else {
+                GuardState::Suspicious { at: glimpse, wait: wait - 1 }
+            };
+            None
+        },
+        GuardState::Investigating { around, mut target, mut searches_left, calm } => {
+            if calm == 0 {
+                guard.state = GuardState::Patrol;
+                return next_goal(guard, game, rng);
+            }
+            if at == target {
+                if searches_left == 0 {
+                    guard.state = GuardState::Patrol;
+                    return next_goal(guard, game, rng);
                 }
-                guard.i = cell.i;
-                guard.j = cell.j;
+                searches_left -= 1;
+                target = search_spot(game, around, at, rng);
+            }
+            guard.state = GuardState::Investigating { around, target, searches_left, calm: calm - 1 };
+            Some(target)
+        },
+        GuardState::Hunting { last_seen, lost } => {
+            if lost >= HUNT_LOST_STEPS {
+                guard.state = GuardState::investigate(last_seen);
+                return next_goal(guard, game, rng);
+            }
+            // `lookout` resets `lost` while the player stays in sight
+            guard.state = GuardState::Hunting { last_seen, lost: lost + 1 };
+            Some(last_seen)
+        },
+    }
+}
 
+// A random cell near `around` for a searching guard to check, other than the one it stands on
+fn search_spot(game: &Game, around: (usize, usize), at: (usize, usize), rng: &mut LevelRng) -> (usize, usize) {
+    let cost = TerrainCost::guard();
+    let distances = Pathfinder::new(&cost).distance_map_within(&game.map, &[around], Some(SEARCH_RADIUS * DIST_BETWEEN));
+    let spots: Vec<(usize, usize)> = distances.within(SEARCH_RADIUS * DIST_BETWEEN).filter(|&spot| spot != at).collect();
+    if spots.is_empty() {
+        around
+    } else {
+        spots[rng.gen_range(0..spots.len())]
+    }
+}
 
-                *transforms.get_mut(guard.entity.unwrap()).unwrap() = Transform {
-                    translation: Vec3::new(guard.i as f32, game.map[guard.j * MAP_SIZE_I + guard.i].height, guard.j as f32),
-                    rotation: Quat::from_rotation_y(rotation),
-                    ..Default::default()
-                };
-                if path.is_empty() {
-                    guard.current_path = None;
-                    continue;
-                }
-            },
-        };
+// Direction to turn to look from `from` towards `to`, along the longer axis
+fn facing_towards(from: (usize, usize), to: (usize, usize)) -> Option<Direction> {
+    let (di, dj) = (to.0 as i32 - from.0 as i32, to.1 as i32 - from.1 as i32);
+    if di == 0 && dj == 0 {
+        None
+    } else if di.abs() >= dj.abs() {
+        Some(if di > 0 { Direction::East } else { Direction::West })
+    } else {
+        Some(if dj > 0 { Direction::South } else { Direction::North })
+    }
+}
+
+// Plan the way to `goal`, starting from where the guard stands at `tick`.
+// Other guards' reservations are respected and the new route is reserved in turn.
+fn plan_leg(guard: &mut Guard, goal: (usize, usize), game: &Game, reservations: &mut ReservationTable, tick: u32) {
+    reservations.release(guard.id);
+    let cost = TerrainCost::guard();
+    guard.current_path = match (game.map.get(guard.i, guard.j), game.map.get(goal.0, goal.1)) {
+        (Some(start), Some(goal)) => Pathfinder::new(&cost).find_path_timed(&game.map, start, goal, tick, reservations, guard.id),
+        _ => None,
+    };
+    match &mut guard.current_path {
+        Some(path) => {
+            reservations.reserve_path(guard.id, path, tick, 1);
+            // the first cell is where the guard already is
+            path.pop();
+            if path.is_empty() {
+                guard.current_path = None;
+            }
+        },
+        None => reservations.reserve(guard.i, guard.j, tick, guard.id),
     }
 }
 
+// Bring in guards until the floor has as many as the difficulty calls for
 fn spawn_guard(
-    mut commands: Commands,
     mut game: ResMut<Game>,
     mut guards: ResMut<GuardRoster>,
+    mut rng: ResMut<GameRng>,
 ){
-    if (guards.guards.len() * 3 <= game.score as usize) {
-        for i in (0..2) {
-            let mut guard: Guard = Guard::default();
-            let mut patrol: Vec<(usize, usize)> = Vec::new();
-            for _ in (0..rand::thread_rng().gen_range(2..=4)) {
-                let i: usize = rand::thread_rng().gen_range(0..MAP_SIZE_I);
-                let j: usize = rand::thread_rng().gen_range(0..MAP_SIZE_J);
-                patrol.push((i, j));
-            }
-            guard.patrol_points = patrol;
-
-            let i: usize = rand::thread_rng().gen_range(0..MAP_SIZE_I);
-            let j: usize = rand::thread_rng().gen_range(0..MAP_SIZE_J);
-            guard.i = i;
-            guard.j = j;
-
-            guard.entity = Some(
-                commands
-                    .spawn_bundle((
-                            Transform {
-                                translation: Vec3::new(guard.i as f32, 0.0, guard.j as f32),
-                                rotation: Quat::from_rotation_y(-std::f32::consts::FRAC_PI_2),
-                                ..Default::default()
-                            },
-                            GlobalTransform::identity(),
-                    ))
-                    .insert(GuardTag)
-                    .with_children(|cell| {
-                        cell.spawn_bundle(PointLightBundle {
-                            point_light: PointLight {
-                                color: Color::rgb(0.5, 0.0, 0.0),
-                                intensity: 5.0,
-                                range: 3.0,
-                                ..Default::default()
-                            },
-                            transform: Transform::from_xyz(0.0, 0.2, 0.0),
-                            ..Default::default()
-                        });
-                        cell.spawn_scene(guards.handle.clone());
-                    })
-                    .id(),
-            );
-            guards.guards.push(guard);
+    let wanted = guards.profile.guards_wanted(game.floor, game.floor_intel);
+    if guards.guards.len() + guards.knocked_out >= wanted {
+        return;
+    }
+    let player = (game.player.i, game.player.j);
+    let mut spots = spawn_spots(&game.map, player, guards.profile.min_spawn_steps);
+    spots.retain(|&spot| guards.guards.iter().all(|guard| (guard.i, guard.j) != spot));
+    let cost = TerrainCost::guard();
+    let walkable: Vec<(usize, usize)> = Pathfinder::new(&cost).distance_map(&game.map, &[player]).reachable().map(|(cell, _)| cell).collect();
+    let tiers: Vec<GuardTier> = guards.profile.tiers_on(game.floor).into_iter().cloned().collect();
+
+    while guards.guards.len() + guards.knocked_out < wanted {
+        // nowhere to walk in unseen, try again once the player has moved on
+        if spots.is_empty() || walkable.is_empty() {
+            return;
         }
+        let patrol = match game.patrols.get(guards.guards.len() + guards.knocked_out) {
+            Some(patrol) => patrol.clone(),
+            None => {
+                let patrol: Vec<(usize, usize)> = (0..rng.0.gen_range(2..=4))
+                    .map(|_| walkable[rng.0.gen_range(0..walkable.len())])
+                    .collect();
+                game.patrols.push(patrol.clone());
+                patrol
+            },
+        };
+        // Routes from the level file start on their first point, when that is a safe place to appear
+        let at = match spots.iter().position(|&spot| spot == patrol[0]) {
+            Some(index) => spots.swap_remove(index),
+            None => spots.swap_remove(rng.0.gen_range(0..spots.len())),
+        };
+        let tier = match tiers.len() {
+            0 => GuardTier { from_floor: 1, vision: GUARD_VISION, model: 0 },
+            count => tiers[rng.0.gen_range(0..count)].clone(),
+        };
+        let guard = Guard {
+            id: guards.next_id,
+            i: at.0,
+            j: at.1,
+            rotation: Direction::default(),
+            current_path: None,
+            pp: 0,
+            patrol_points: patrol,
+            state: GuardState::Patrol,
+            vision: tier.vision,
+            model: tier.model,
+        };
+        guards.next_id += 1;
+        guards.guards.push(guard);
     }
 }
 
+// Spawn the guard's model and light, placed by `draw_guards`
+fn spawn_guard_model(commands: &mut Commands, guard: &Guard, model: Handle<Scene>) {
+    let mut light = None;
+    let entity = commands
+        .spawn_bundle((Transform::from_scale(Vec3::ZERO), GlobalTransform::identity()))
+        .insert(GuardTag)
+        .with_children(|cell| {
+            light = Some(cell.spawn_bundle(PointLightBundle {
+                point_light: PointLight {
+                    color: Color::rgb(0.5, 0.0, 0.0),
+                    intensity: 0.0,
+                    range: 3.0,
+                    ..Default::default()
+                },
+                transform: Transform::from_xyz(0.0, 0.2, 0.0),
+                ..Default::default()
+            }).id());
+            cell.spawn_scene(model);
+        })
+        .id();
+    commands.entity(entity).insert(GuardModel { id: guard.id, light });
+}
+
+// Cells a guard can walk in on: reachable from the player, at least `min_steps` away and out of their line of sight
+pub fn spawn_spots(grid: &Grid, player: (usize, usize), min_steps: i32) -> Vec<(usize, usize)> {
+    let cost = TerrainCost::guard();
+    Pathfinder::new(&cost).distance_map(grid, &[player]).reachable()
+        .filter(|&(spot, cost)| cost >= min_steps * DIST_BETWEEN && !vision::line_of_sight(grid, player, spot))
+        .map(|(spot, _)| spot)
+        .collect()
+}
+
 fn lookout (
-    mut commands: Commands,
     mut game: ResMut<Game>,
     mut guards: ResMut<GuardRoster>,
     mut state: ResMut<State<GameState>>,
+    mut clock: ResMut<SimClock>,
 ) {
+    let player = (game.player.i, game.player.j);
     for guard in guards.guards.iter_mut() {
-        match guard.rotation {
-            // 0 -> j-
-            0 => {
-                for i in (0..=VISION) {
-                    if (guard.j as i32 - i as i32) < 0 || !game.map[(guard.j - i) * MAP_SIZE_I + guard.i].open_sides[0] {
-                        break;
-                    }
-                    if game.player.j == guard.j - i && game.player.i == guard.i {
-                        let _ = state.overwrite_set(GameState::GameOver);
-                        return;
-                    }
-                }
-            },
-            1 => {
-                for i in (0..=VISION) {
-                    if guard.i + i >= MAP_SIZE_I || !game.map[guard.j * MAP_SIZE_I + guard.i + i].open_sides[1] {
-                        break;
-                    }
-                    if game.player.i == guard.i + i && game.player.j == guard.j{
-                        let _ = state.overwrite_set(GameState::GameOver);
-                        return;
-                    }
-                }
-            },
-            2 => {
-                for i in (0..=VISION) {
-                    if guard.j + i >= MAP_SIZE_J || !game.map[(guard.j + i) * MAP_SIZE_I + guard.i].open_sides[2] {
-                        break;
-                    }
-                    if game.player.j == guard.j + i && game.player.i == guard.i {
-                        let _ = state.overwrite_set(GameState::GameOver);
-                        return;
-                    }
-                }
-            },
-            3 => {
-                for i in (0..=VISION) {
-                    if (guard.i as i32 - i as i32) < 0 || !game.map[guard.j * MAP_SIZE_I + guard.i - i].open_sides[3] {
-                        break;
-                    }
-                    if game.player.i == guard.i - i && game.player.j == guard.j {
-                        let _ = state.overwrite_set(GameState::GameOver);
-                        return;
-                    }
-                }
+        guard.state = match (sight_player(guard, &game), guard.state) {
+            (None, state) => state,
+            (Some(Sighting::Spotted), _) | (Some(Sighting::Glimpse), GuardState::Hunting { .. }) => GuardState::Hunting { last_seen: player, lost: 0 },
+            (Some(Sighting::Glimpse), GuardState::Patrol) => GuardState::Suspicious { at: player, wait: SUSPICION_STEPS },
+            (Some(Sighting::Glimpse), GuardState::Suspicious { wait, .. }) => GuardState::Suspicious { at: player, wait },
+            (Some(Sighting::Glimpse), GuardState::Investigating { .. }) => GuardState::investigate(player),
+        };
+        // a hunter that reaches the player catches them
+        let next_to = (guard.i as i32 - player.0 as i32).abs() + (guard.j as i32 - player.1 as i32).abs() <= 1;
+        if matches!(guard.state, GuardState::Hunting { .. }) && next_to && vision::line_of_sight(&game.map, (guard.i, guard.j), player) {
+            let _ = state.overwrite_set(GameState::GameOver);
+            clock.halted = true;
+            return;
+        }
+    }
+}
+
+// Look for the player, the far edge of the cone and shadows only give a glimpse
+fn sight_player(guard: &Guard, game: &Game) -> Option<Sighting> {
+    let player = (game.player.i, game.player.j);
+    if !vision::can_see(&game.map, (guard.i, guard.j), guard.rotation, &guard.vision, player) {
+        return None;
+    }
+    let (dx, dy) = (player.0 as f32 - guard.i as f32, player.1 as f32 - guard.j as f32);
+    let distance = (dx * dx + dy * dy).sqrt();
+    let shadow = game.map.get(player.0, player.1).map_or(false, |cell| cell.terrain == Terrain::Shadow);
+    if distance > guard.vision.range - 1.0 || (shadow && distance > 1.5) {
+        Some(Sighting::Glimpse)
+    } else {
+        Some(Sighting::Spotted)
+    }
+}
+
+// A clicked walk stops as soon as a guard the player didn't see before comes into view
+fn interrupt_walk(mut game: ResMut<Game>, mut guards: ResMut<GuardRoster>) {
+    let player = (game.player.i, game.player.j);
+    let in_view: Vec<usize> = guards.guards.iter()
+        .filter(|guard| vision::can_see(&game.map, player, Direction::North, &PLAYER_VISION, (guard.i, guard.j)))
+        .map(|guard| guard.id)
+        .collect();
+    if in_view.iter().any(|id| !guards.in_view.contains(id)) {
+        game.player.route.clear();
+    }
+    guards.in_view = in_view;
+}
+
+// The fog remembers where guards were last seen
+fn remember_guards(guards: Res<GuardRoster>, mut fog: ResMut<FogOfWar>) {
+    for guard in guards.guards.iter() {
+        if fog.is_visible(guard.i, guard.j) && fog.last_seen.get(&guard.id) != Some(&(guard.i, guard.j)) {
+            fog.last_seen.insert(guard.id, (guard.i, guard.j));
+        }
+    }
+}
+
+// Models follow the roster: new guards get one, knocked out guards lose theirs, the others stand on
+// their cell facing their way. Guards out of the player's view are hidden
+fn draw_guards(
+    mut commands: Commands,
+    guards: Res<GuardRoster>,
+    models: Res<GuardModels>,
+    game: Res<Game>,
+    fog: Res<FogOfWar>,
+    mut drawn: Query<(Entity, &GuardModel, &mut Transform)>,
+    mut lights: Query<&mut PointLight>,
+) {
+    for (entity, model, mut transform) in drawn.iter_mut() {
+        let guard = match guards.guards.iter().find(|guard| guard.id == model.id) {
+            Some(guard) => guard,
+            None => {
+                commands.entity(entity).despawn_recursive();
+                continue;
             },
-            _ => (),
+        };
+        let seen = fog.is_visible(guard.i, guard.j);
+        let height = game.map.get(guard.i, guard.j).map_or(0.0, |cell| cell.height);
+        *transform = Transform {
+            translation: Vec3::new(guard.i as f32, height, guard.j as f32),
+            rotation: Quat::from_rotation_y(guard.rotation.yaw()),
+            scale: if seen { Vec3::ONE } else { Vec3::ZERO },
+        };
+        if let Some(mut light) = model.light.and_then(|light| lights.get_mut(light).ok()) {
+            light.intensity = if seen { 5.0 } else { 0.0 };
+        }
+    }
+    for guard in guards.guards.iter().filter(|guard| drawn.iter().all(|(_, model, _)| model.id != guard.id)) {
+        let model = models.0.get(guard.model).or_else(|| models.0.first()).cloned().unwrap_or_default();
+        spawn_guard_model(&mut commands, guard, model);
+    }
+}
+
+// `T` knocks out a guard standing next to the player that isn't looking their way and takes its keycard. It makes some noise
+fn takedown(
+    mut game: ResMut<Game>,
+    mut guards: ResMut<GuardRoster>,
+    mut turn: ResMut<Turn>,
+    mut noises: EventWriter<Noise>,
+    mut fog: ResMut<FogOfWar>,
+    input: Res<PlayerInput>,
+) {
+    if !input.takedown || !turn.player_can_act() {
+        return;
+    }
+    let player = (game.player.i, game.player.j);
+    let target = guards.guards.iter().position(|guard| {
+        let next_to = (guard.i as i32 - player.0 as i32).abs() + (guard.j as i32 - player.1 as i32).abs() == 1;
+        next_to
+            && vision::line_of_sight(&game.map, player, (guard.i, guard.j))
+            && !vision::can_see(&game.map, (guard.i, guard.j), guard.rotation, &guard.vision, player)
+            && !matches!(guard.state, GuardState::Hunting { .. })
+    });
+    let index = match target {
+        Some(index) if turn.spend(TAKEDOWN_ACTION_POINTS) => index,
+        _ => return,
+    };
+    let guard = guards.guards.remove(index);
+    guards.reservations.release(guard.id);
+    guards.knocked_out += 1;
+    // their keycard opens locked doors
+    game.player.keycard = true;
+    fog.last_seen.remove(&guard.id);
+    noises.send(Noise { at: (guard.i, guard.j), radius: noise::TAKEDOWN_NOISE });
+    if turn.mode == PlayMode::TurnBased && turn.action_points == 0 {
+        turn.end_player_turn();
+    }
+}
+
+// Guards that hear a noise go and look where it came from, unless they're already chasing someone
+fn hear_noise(game: Res<Game>, mut guards: ResMut<GuardRoster>, mut noises: EventReader<Noise>) {
+    for heard in noises.iter() {
+        let reach = noise::noise_reach(&game.map, heard);
+        for guard in guards.guards.iter_mut() {
+            if reach.is_reachable(guard.i, guard.j) && !matches!(guard.state, GuardState::Hunting { .. }) {
+                guard.state = GuardState::investigate(heard.at);
+            }
         }
     }
 }
+
+// Cells the overlay tints, each guard sees as far as its tier does
+pub fn danger_tiles(map: &Grid, views: &[(WatcherView, VisionParams)]) -> DangerTiles {
+    let mut tiles = DangerTiles::default();
+    for (view, params) in views {
+        let seen = vision::danger_tiles(map, std::slice::from_ref(view), params);
+        tiles.watched.extend(seen.watched);
+        tiles.upcoming.extend(seen.upcoming);
+    }
+    let watched = &tiles.watched;
+    tiles.upcoming.retain(|cell| !watched.contains(cell));
+    tiles
+}
+
+// Floor tint over what the guards see now and on their next moves
+#[derive(Default)]
+struct DangerOverlay {
+    shown: bool,
+    // Guard views and door revision the tiles were drawn for, redrawn when they change
+    views: Option<(Vec<(WatcherView, VisionParams)>, u32)>,
+    markers: Vec<Entity>,
+    mesh: Handle<Mesh>,
+    watched: Handle<StandardMaterial>,
+    upcoming: Handle<StandardMaterial>,
+}
+
+fn setup_danger(
+    mut overlay: ResMut<DangerOverlay>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let mut material = |color: Color| materials.add(StandardMaterial {
+        base_color: color,
+        unlit: true,
+        ..Default::default()
+    });
+    *overlay = DangerOverlay {
+        shown: overlay.shown,
+        mesh: meshes.add(Mesh::from(shape::Plane { size: 0.8 })),
+        watched: material(Color::rgb(0.8, 0.1, 0.1)),
+        upcoming: material(Color::rgb(0.45, 0.2, 0.1)),
+        ..Default::default()
+    };
+}
+
+fn toggle_danger(mut overlay: ResMut<DangerOverlay>, keyboard_input: Res<Input<KeyCode>>) {
+    if keyboard_input.just_pressed(KeyCode::V) {
+        overlay.shown = !overlay.shown;
+        overlay.views = None;
+    }
+}
+
+fn show_danger(
+    mut commands: Commands,
+    mut overlay: ResMut<DangerOverlay>,
+    game: Res<Game>,
+    guards: Res<GuardRoster>,
+    fog: Res<FogOfWar>,
+    turn: Res<Turn>,
+) {
+    let views = if overlay.shown {
+        guards.danger_views(&fog, turn.mode)
+    } else {
+        Vec::new()
+    };
+    let revision = game.map.door_revision();
+    if overlay.views.as_ref().map_or(false, |(drawn, drawn_revision)| *drawn == views && *drawn_revision == revision) {
+        return;
+    }
+    for entity in overlay.markers.drain(..) {
+        commands.entity(entity).despawn();
+    }
+    let tiles = danger_tiles(&game.map, &views);
+    let cells = tiles.watched.iter().map(|&cell| (cell, overlay.watched.clone()))
+        .chain(tiles.upcoming.iter().map(|&cell| (cell, overlay.upcoming.clone())))
+        .collect::<Vec<_>>();
+    for ((i, j), material) in cells {
+        let height = game.map.get(i, j).map_or(0.0, |cell| cell.height);
+        let marker = commands.spawn_bundle(PbrBundle {
+            mesh: overlay.mesh.clone(),
+            material,
+            transform: Transform::from_xyz(i as f32, height + 0.03, j as f32),
+            ..Default::default()
+        })
+        .insert(LevelTag)
+        .id();
+        overlay.markers.push(marker);
+    }
+    overlay.views = Some((views, revision));
+}
@@ -0,0 +1,129 @@
+use std::{cmp::Reverse, fs, io, path::{Path, PathBuf}, time::{SystemTime, UNIX_EPOCH}};
+use bevy::prelude::*;
+use serde::{Serialize, Deserialize};
+use ron::ser::PrettyConfig;
+use crate::{difficulty::Difficulty, storage};
+
+// Entries kept on the board
+pub const LEADERBOARD_SIZE: usize = 10;
+const LEADERBOARD_FILE: &str = "leaderboard.ron";
+// Longest name the player can type
+pub const MAX_NAME_LENGTH: usize = 12;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScoreEntry {
+    pub name: String,
+    pub score: i32,
+    // Seconds since the Unix epoch
+    pub date: u64,
+    pub floor: u32,
+    pub seed: u64,
+    pub difficulty: Difficulty,
+}
+
+// Best runs on this machine, best first. Kept in `leaderboard.ron` in the data folder
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Leaderboard {
+    pub entries: Vec<ScoreEntry>,
+    // Offered again the next time a name is asked for
+    #[serde(default)]
+    pub last_name: String,
+}
+
+impl ScoreEntry {
+    // Higher score first, then the higher floor, then whoever got there first
+    fn rank_key(&self) -> (i32, u32, Reverse<u64>) {
+        (self.score, self.floor, Reverse(self.date))
+    }
+}
+
+impl Leaderboard {
+    pub fn path() -> PathBuf {
+        storage::data_dir().join(LEADERBOARD_FILE)
+    }
+
+    // The board saved at `path`. A damaged file is set aside next to it and the board starts over
+    pub fn load<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref();
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Leaderboard::default(),
+            Err(err) => {
+                warn!("{}: {}, starting a new leaderboard", path.display(), err);
+                return Leaderboard::default();
+            },
+        };
+        match ron::de::from_str::<Leaderboard>(&text) {
+            Ok(mut board) => {
+                board.entries.sort_by_key(|entry| Reverse(entry.rank_key()));
+                board.entries.truncate(LEADERBOARD_SIZE);
+                board
+            },
+            Err(err) => {
+                let mut backup = path.as_os_str().to_owned();
+                backup.push(".corrupt");
+                warn!("{}: {}, moved to {:?} and starting a new leaderboard", path.display(), err, backup);
+                let _ = fs::rename(path, &backup);
+                Leaderboard::default()
+            },
+        }
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let text = ron::ser::to_string_pretty(self, PrettyConfig::new())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        storage::write_atomic(path, &text)
+    }
+
+    // Whether a run with `score` would make it on the board
+    pub fn qualifies(&self, score: i32, floor: u32) -> bool {
+        self.entries.len() < LEADERBOARD_SIZE
+            || self.entries.last().map_or(true, |last| (score, floor) > (last.score, last.floor))
+    }
+
+    // Add the entry in its place, returns its rank from 0 when it stays on the board
+    pub fn insert(&mut self, entry: ScoreEntry) -> Option<usize> {
+        let rank = self.entries.iter().position(|other| entry.rank_key() > other.rank_key()).unwrap_or(self.entries.len());
+        self.entries.insert(rank, entry);
+        self.entries.truncate(LEADERBOARD_SIZE);
+        if rank < LEADERBOARD_SIZE {
+            Some(rank)
+        } else {
+            None
+        }
+    }
+
+    // One line per entry, the one at `highlight` marked
+    pub fn table(&self, highlight: Option<usize>) -> String {
+        if self.entries.is_empty() {
+            return "No scores yet".to_string();
+        }
+        self.entries.iter().enumerate().map(|(rank, entry)| {
+            format!(
+                "{}{:>2}. {:<12} {:>4} intel  floor {:<3} {:<6} {}  seed {}",
+                if Some(rank) == highlight { ">" } else { " " },
+                rank + 1, entry.name, entry.score, entry.floor, entry.difficulty.name(), date_string(entry.date), entry.seed,
+            )
+        }).collect::<Vec<_>>().join("\n")
+    }
+}
+
+// Seconds since the Unix epoch right now
+pub fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs())
+}
+
+// `YYYY-MM-DD` of a time in seconds since the Unix epoch, in UTC
+pub fn date_string(secs: u64) -> String {
+    // days to a civil date, after Howard Hinnant's `civil_from_days`
+    let z = (secs / 86_400) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
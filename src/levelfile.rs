@@ -0,0 +1,116 @@
+use std::{fmt, fs, io, path::Path, str::FromStr};
+use serde::{Serialize, Deserialize};
+use ron::ser::PrettyConfig;
+use crate::{Cell, grid::Grid, levelgen, storage};
+
+// Hand editable description of a level, stored as RON under `assets/levels/`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LevelFile {
+    pub width: usize,
+    pub length: usize,
+    pub spawn: (usize, usize),
+    // Patrol route of each guard, the guard starts on the first point
+    #[serde(default)]
+    pub patrols: Vec<Vec<(usize, usize)>>,
+    // Intel locations, used in order
+    #[serde(default)]
+    pub bonuses: Vec<(usize, usize)>,
+    // Elevator out of the level, the reachable cell furthest from the spawn when left out
+    #[serde(default)]
+    pub exit: Option<(usize, usize)>,
+    // Row by row, `j * width + i`
+    pub cells: Vec<Cell>,
+}
+
+#[derive(Debug)]
+pub enum LevelFileError {
+    Io(io::Error),
+    Format(ron::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for LevelFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LevelFileError::Io(err) => write!(f, "{}", err),
+            LevelFileError::Format(err) => write!(f, "{}", err),
+            LevelFileError::Invalid(reason) => write!(f, "invalid level: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for LevelFileError {}
+
+impl From<io::Error> for LevelFileError {
+    fn from(err: io::Error) -> Self {
+        LevelFileError::Io(err)
+    }
+}
+
+impl From<ron::Error> for LevelFileError {
+    fn from(err: ron::Error) -> Self {
+        LevelFileError::Format(err)
+    }
+}
+
+impl FromStr for LevelFile {
+    type Err = LevelFileError;
+
+    fn from_str(text: &str) -> Result<Self, LevelFileError> {
+        let level: LevelFile = ron::de::from_str(text)?;
+        level.check()?;
+        Ok(level)
+    }
+}
+
+impl LevelFile {
+    pub fn to_string(&self) -> Result<String, LevelFileError> {
+        // One cell per line keeps the file diffable and easy to tweak by hand
+        let config = PrettyConfig::new().depth_limit(2);
+        Ok(ron::ser::to_string_pretty(self, config)?)
+    }
+
+    // Make sure every coordinate in the file points inside the map and every cell can be walked to
+    fn check(&self) -> Result<(), LevelFileError> {
+        if self.cells.len() != self.width * self.length {
+            return Err(LevelFileError::Invalid(format!(
+                "expected {} cells for a {}x{} map, found {}",
+                self.width * self.length, self.width, self.length, self.cells.len(),
+            )));
+        }
+        if let Some((index, cell)) = self.cells.iter().enumerate().find(|(index, cell)| cell.j * self.width + cell.i != *index) {
+            return Err(LevelFileError::Invalid(format!("cell {} is at ({}, {})", index, cell.i, cell.j)));
+        }
+        let points = std::iter::once(&self.spawn)
+            .chain(self.patrols.iter().flatten())
+            .chain(self.bonuses.iter())
+            .chain(self.exit.iter());
+        for &(i, j) in points {
+            if i >= self.width || j >= self.length {
+                return Err(LevelFileError::Invalid(format!("({}, {}) is outside the map", i, j)));
+            }
+        }
+        if self.exit == Some(self.spawn) {
+            return Err(LevelFileError::Invalid("the exit is on the spawn point".to_string()));
+        }
+        if self.patrols.iter().any(|patrol| patrol.is_empty()) {
+            return Err(LevelFileError::Invalid("empty patrol route".to_string()));
+        }
+        let report = levelgen::validate_level(&Grid::new(self.width, self.length, self.cells.clone()), self.spawn);
+        if let Some(&(i, j)) = report.unreachable.first() {
+            return Err(LevelFileError::Invalid(format!(
+                "{} cells can't be reached from the spawn, like ({}, {})", report.unreachable.len(), i, j,
+            )));
+        }
+        Ok(())
+    }
+}
+
+pub fn load_level<P: AsRef<Path>>(path: P) -> Result<LevelFile, LevelFileError> {
+    fs::read_to_string(path)?.parse()
+}
+
+pub fn save_level<P: AsRef<Path>>(path: P, level: &LevelFile) -> Result<(), LevelFileError> {
+    storage::write_atomic(path, &level.to_string()?)?;
+    Ok(())
+}
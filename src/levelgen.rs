@@ -1,7 +1,25 @@
-use rand::{Rng, prelude::{thread_rng, ThreadRng}};
-use crate::Cell;
+use rand::{Rng, SeedableRng, prelude::thread_rng};
+use rand_chacha::ChaCha8Rng;
+use crate::{Cell, Terrain, DoorState, grid::{Grid, Direction}, pathfinding};
 
 const MANY_DOORS: bool = true;
+// Odds of a cell getting special terrain
+const SHADOW_CHANCE: f64 = 0.08;
+const VENT_CHANCE: f64 = 0.03;
+const HAZARD_CHANCE: f64 = 0.03;
+// Share of the doors that start locked
+const LOCKED_DOOR_CHANCE: f64 = 0.2;
+
+// Portable seedable rng, the same seed gives the same run on every platform
+pub type LevelRng = ChaCha8Rng;
+
+// Random source of `floor` in the run seeded with `seed`. Each floor draws from its own stream of the
+// seed so no two seeds share a floor, the first one is what `generate_level` uses
+pub fn floor_rng(seed: u64, floor: u32) -> LevelRng {
+    let mut rng = LevelRng::seed_from_u64(seed);
+    rng.set_stream(floor.saturating_sub(1) as u64);
+    rng
+}
 
 struct Leaf {
     x1: usize,
@@ -27,19 +45,35 @@ impl Leaf {
     }
 }
 
+// Generate a level from `seed`, or from a fresh random seed when `None`.
+// The seed used is returned with the map so the layout can be reproduced.
 pub fn generate_level(
     level_width: usize,
     level_length: usize,
     min_section_size: usize,
     min_room_size: usize,
-) -> Vec<Cell> {
-    let mut rng = thread_rng();
+    seed: Option<u64>,
+) -> (Grid, u64) {
+    let seed = seed.unwrap_or_else(|| thread_rng().gen());
+    let mut rng = LevelRng::seed_from_u64(seed);
+    let map = generate_level_with(level_width, level_length, min_section_size, min_room_size, &mut rng);
+    (map, seed)
+}
+
+// Generate a level drawing from `rng`, so the caller can keep using the same source afterwards
+pub fn generate_level_with<R: Rng>(
+    level_width: usize,
+    level_length: usize,
+    min_section_size: usize,
+    min_room_size: usize,
+    rng: &mut R,
+) -> Grid {
     let hall_width: usize = 2;
 
     let mut root: Leaf = Leaf::new(0, 0, level_width, level_length);
     let start_vertical: bool = rng.gen();
-    create_rooms(&mut root, start_vertical, min_section_size, hall_width, &mut rng);
-    create_rooms(&mut root, start_vertical, min_room_size, 0, &mut rng);
+    create_rooms(&mut root, start_vertical, min_section_size, hall_width, rng);
+    create_rooms(&mut root, start_vertical, min_room_size, 0, rng);
     let mut map = (0..level_length).map(|j| {
         (0..level_width).map(|i| {
             Cell {
@@ -51,16 +85,21 @@ pub fn generate_level(
             }
         }).collect::<Vec<Cell>>()
     }).flatten().collect::<Vec<Cell>>();
-    build_map(&root, &mut map, &mut rng, level_width, level_length);
-    return map;
+    build_map(&root, &mut map, rng, level_width, level_length);
+    let mut grid = Grid::new(level_width, level_length, map);
+    let spawn = spawn_point(level_width, level_length);
+    repair_level(&mut grid, spawn, rng);
+    scatter_terrain(&mut grid, rng, spawn);
+    shut_doors(&mut grid, rng);
+    grid
 }
 
-fn create_rooms(
+fn create_rooms<R: Rng>(
     parent: &mut Leaf,
     vertical: bool,
     min_size: usize,
     hall_width: usize,
-    rng: &mut ThreadRng,
+    rng: &mut R,
 ) {
     if !parent.is_leaf { create_rooms(parent.left_child.as_mut().unwrap(), !vertical, min_size, hall_width, rng);
         create_rooms(parent.right_child.as_mut().unwrap(), !vertical, min_size, hall_width, rng);
@@ -93,7 +132,7 @@ fn create_rooms(
     parent.right_child = Some(Box::new(right_child));
 }
 
-fn build_map(leaf: &Leaf, map: &mut Vec<Cell>, rng: &mut ThreadRng, map_width: usize, map_length: usize) {
+fn build_map<R: Rng>(leaf: &Leaf, map: &mut Vec<Cell>, rng: &mut R, map_width: usize, map_length: usize) {
     if !leaf.is_leaf {
         build_map(leaf.left_child.as_ref().unwrap(), map, rng, map_width, map_length);
         build_map(leaf.right_child.as_ref().unwrap(), map, rng, map_width, map_length);
@@ -141,3 +180,123 @@ fn build_map(leaf: &Leaf, map: &mut Vec<Cell>, rng: &mut ThreadRng, map_width: u
         map[map_width * i + leaf.x1].doors[3] = i == door;
     }
 }
+
+fn scatter_terrain<R: Rng>(grid: &mut Grid, rng: &mut R, spawn: (usize, usize)) {
+    let cells: Vec<(usize, usize)> = grid.iter().map(|cell| (cell.i, cell.j)).filter(|&at| at != spawn).collect();
+    for (i, j) in cells {
+        let roll: f64 = rng.gen();
+        let terrain = if roll < SHADOW_CHANCE {
+            Terrain::Shadow
+        } else if roll < SHADOW_CHANCE + VENT_CHANCE {
+            Terrain::Vent
+        } else if roll < SHADOW_CHANCE + VENT_CHANCE + HAZARD_CHANCE {
+            Terrain::Hazard
+        } else {
+            Terrain::Floor
+        };
+        if let Some(cell) = grid.get_mut(i, j) {
+            cell.terrain = terrain;
+        }
+    }
+}
+
+// Every door starts closed, some of them locked
+fn shut_doors<R: Rng>(grid: &mut Grid, rng: &mut R) {
+    for (i, j, dir) in grid.door_edges() {
+        let state = if rng.gen_bool(LOCKED_DOOR_CHANCE) { DoorState::Locked } else { DoorState::Closed };
+        grid.set_door(i, j, dir, state);
+    }
+}
+
+// Cell the player starts the level on
+pub fn spawn_point(level_width: usize, level_length: usize) -> (usize, usize) {
+    (level_width / 2, level_length / 2)
+}
+
+// Cell of the elevator out of the level, the walk furthest from `spawn`. Doors count as passable
+pub fn exit_point(grid: &Grid, spawn: (usize, usize)) -> (usize, usize) {
+    pathfinding::distance_map(grid, &[spawn]).reachable()
+        .max_by_key(|&((i, j), cost)| (cost, std::cmp::Reverse((j, i))))
+        .map_or(spawn, |(cell, _)| cell)
+}
+
+// Outcome of flood filling a level from the spawn point
+#[derive(Debug, Clone, PartialEq)]
+pub struct LevelReport {
+    pub width: usize,
+    pub length: usize,
+    pub spawn: (usize, usize),
+    // Indexed like the map, true when the cell can be walked to from the spawn and back
+    pub reachable: Vec<bool>,
+    pub unreachable: Vec<(usize, usize)>,
+}
+
+impl LevelReport {
+    pub fn is_connected(&self) -> bool {
+        self.unreachable.is_empty()
+    }
+
+    pub fn is_reachable(&self, i: usize, j: usize) -> bool {
+        i < self.width && j < self.length && self.reachable[j * self.width + i]
+    }
+}
+
+// Flood fill the map from `spawn` through the passages the player walks, and report every cell that can't be reached
+pub fn validate_level(map: &Grid, spawn: (usize, usize)) -> LevelReport {
+    let mut reachable = vec![false; map.len()];
+    // a spawn off the map reaches nothing
+    if let Some(start) = map.index(spawn.0, spawn.1) {
+        let mut stack = vec![spawn];
+        reachable[start] = true;
+        while let Some((i, j)) = stack.pop() {
+            for (_, next) in map.passages(i, j) {
+                match map.index(next.i, next.j) {
+                    Some(index) if !reachable[index] => {
+                        reachable[index] = true;
+                        stack.push((next.i, next.j));
+                    },
+                    _ => {},
+                }
+            }
+        }
+    }
+
+    let unreachable = map.iter().zip(&reachable)
+        .filter(|(_, &reached)| !reached)
+        .map(|(cell, _)| (cell.i, cell.j))
+        .collect();
+    LevelReport { width: map.width(), length: map.length(), spawn, reachable, unreachable }
+}
+
+// Carve doors between cells reachable from `spawn` and sealed off ones until the whole map is connected.
+// Returns the number of doors added, it stops early when nothing is reachable to carve from, like with a
+// spawn off the map
+pub fn repair_level<R: Rng>(map: &mut Grid, spawn: (usize, usize), rng: &mut R) -> usize {
+    let mut carved = 0;
+    loop {
+        let report = validate_level(map, spawn);
+        if report.is_connected() {
+            return carved;
+        }
+        // Every wall separating the reachable area from the rest
+        let mut candidates: Vec<((usize, usize), Direction, (usize, usize))> = Vec::new();
+        for cell in map.iter().filter(|cell| report.is_reachable(cell.i, cell.j)) {
+            for dir in Direction::ALL {
+                if let Some(next) = map.step(cell.i, cell.j, dir).filter(|&(x, y)| !report.is_reachable(x, y)) {
+                    candidates.push(((cell.i, cell.j), dir, next));
+                }
+            }
+        }
+        if candidates.is_empty() {
+            return carved;
+        }
+        let (from, dir, to) = candidates[rng.gen_range(0..candidates.len())];
+        for ((i, j), side) in [(from, dir), (to, dir.opposite())] {
+            if let Some(cell) = map.get_mut(i, j) {
+                cell.open_sides[side.index()] = true;
+                cell.doors[side.index()] = true;
+            }
+        }
+        carved += 1;
+    }
+}
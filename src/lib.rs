@@ -1,20 +1,86 @@
 #![allow(unused)]
 use bevy::prelude::*;
+use serde::{Serialize, Deserialize};
 pub mod game;
 pub mod consts;
+pub mod grid;
 pub mod levelgen;
+pub mod levelfile;
 pub mod splash;
 pub mod menu;
 pub mod guard;
 pub mod pathfinding;
+pub mod turn;
+pub mod route;
+pub mod vision;
+pub mod noise;
+pub mod fog;
+pub mod door;
+pub mod difficulty;
+pub mod storage;
+pub mod settings;
+pub mod leaderboard;
+pub mod save;
+pub mod sim;
+pub mod replay;
 
-#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct Cell {
     pub open_sides: [bool; 4],
     pub doors: [bool; 4],
     pub height: f32,
     pub i: usize,
     pub j: usize,
+    #[serde(default)]
+    pub terrain: Terrain,
+    // State of the door on each side, only meaningful where `doors` is set
+    #[serde(default)]
+    pub door_states: [DoorState; 4],
+}
+
+impl Cell {
+    // State of the door on side `dir`, `None` when there is no doorway there
+    pub fn door(&self, dir: grid::Direction) -> Option<DoorState> {
+        if self.doors[dir.index()] {
+            Some(self.door_states[dir.index()])
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DoorState {
+    Open,
+    // Blocks movement and sight, anyone can open it
+    Closed,
+    // Needs a keycard or a hack before it opens
+    Locked,
+}
+
+// Levels saved before doors had a state keep them open
+impl Default for DoorState {
+    fn default() -> Self {
+        DoorState::Open
+    }
+}
+
+// What the floor of a cell is made of, agents weigh these differently when planning a route
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Terrain {
+    Floor,
+    // Dark corner, good for sneaking
+    Shadow,
+    // Crawlspace the player fits through
+    Vent,
+    // Spilled chemicals, exposed wiring...
+    Hazard,
+}
+
+impl Default for Terrain {
+    fn default() -> Self {
+        Terrain::Floor
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -23,6 +89,10 @@ pub enum GameState {
     Menu,
     Splash,
     GameOver,
+    // Between two floors, or before a floor is played again, the last one is torn down before the next is built
+    NextFloor,
+    // Pushed over `Game` with `Escape`, the level stands still under the pause menu
+    Paused,
 }
 
 // Generic system that takes a component as a parameter, and will despawn all entities with that component
@@ -1,17 +1,66 @@
 use bevy::prelude::*;
-use errio::{GameState, game::GamePlugin, menu::MenuPlugin, splash::SplashPlugin};
+use std::path::PathBuf;
+use errio::{GameState, game::{GamePlugin, GameViewPlugin, LevelConfig}, turn::{Turn, PlayMode}, menu::{MenuPlugin, PausePlugin}, splash::SplashPlugin, settings::{Settings, SettingsPlugin}, leaderboard::Leaderboard, save::{Restore, SavePlugin}, replay::{Replay, Playback, ReplayPlugin, ReplayFilesPlugin}};
 
 fn main() {
+    let settings = Settings::load(Settings::path());
+    let (mut config, mode, replay) = parse_args(settings.gameplay.mode);
+    let replay = replay.map(|path| Replay::load(&path).unwrap_or_else(|err| {
+        eprintln!("{}: {}", path.display(), err);
+        std::process::exit(1);
+    }));
+    // a replay goes straight to its run, as it was set up when recorded
+    let mut restore = Restore::default();
+    let mut state = GameState::Splash;
+    if let Some(replay) = &replay {
+        config.width = replay.size.0;
+        config.length = replay.size.1;
+        restore = replay.start.clone().into();
+        state = GameState::Game;
+    }
     App::new()
+        .insert_resource(settings.window_descriptor())
+        .insert_resource(config)
+        .insert_resource(Turn::new(mode))
+        .insert_resource(settings.gameplay.difficulty)
+        .insert_resource(settings)
+        .insert_resource(Leaderboard::load(Leaderboard::path()))
+        .insert_resource(restore)
+        .insert_resource(replay.map(Playback::new).unwrap_or_default())
         .add_plugins(DefaultPlugins)
-        .add_state(GameState::Splash)
+        .add_plugin(SettingsPlugin)
+        .add_state(state)
         .add_startup_system(setup_cameras)
         .add_plugin(MenuPlugin)
+        .add_plugin(PausePlugin)
         .add_plugin(SplashPlugin)
         .add_plugin(GamePlugin)
+        .add_plugin(GameViewPlugin)
+        .add_plugin(SavePlugin)
+        .add_plugin(ReplayPlugin)
+        .add_plugin(ReplayFilesPlugin)
         .run();
 }
 
 fn setup_cameras(mut commands: Commands) {
     commands.spawn_bundle(UiCameraBundle::default());
 }
+
+// Read `--seed <n>` to replay a shared layout, `--level <file>` to play a curated one,
+// `--realtime` to let the guards move on their own clock whatever the settings say
+// and `--replay <file>` to watch a recorded run
+fn parse_args(mut mode: PlayMode) -> (LevelConfig, PlayMode, Option<PathBuf>) {
+    let mut config = LevelConfig::default();
+    let mut replay = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" => config.seed = args.next().and_then(|seed| seed.parse().ok()),
+            "--level" => config.file = args.next().map(PathBuf::from),
+            "--realtime" => mode = PlayMode::RealTime,
+            "--replay" => replay = args.next().map(PathBuf::from),
+            _ => {}
+        }
+    }
+    (config, mode, replay)
+}
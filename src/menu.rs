@@ -1,11 +1,13 @@
 use bevy::{app::AppExit, prelude::*};
-use crate::{despawn_entities, GameState, consts::fonts};
+use crate::{despawn_entities, GameState, consts::fonts, difficulty::Difficulty, turn::PlayMode, settings::{Settings, SettingsChanged, WindowModeSetting}, leaderboard::Leaderboard, save::{self, Restore, RunSnapshot}, game::FloorStart};
 
 const TEXT_COLOR: Color = Color::rgb(0.9, 0.9, 0.9);
 const NORMAL_BUTTON: Color = Color::rgb(0.15, 0.15, 0.15);
 const HOVERED_BUTTON: Color = Color::rgb(0.35, 0.35, 0.35);
 const HOVERED_PRESSED_BUTTON: Color = Color::rgb(0.25, 0.65, 0.25);
 const PRESSED_BUTTON: Color = Color::rgb(0.35, 0.75, 0.35);
+// The level shows through the pause menu
+const PAUSE_BACKGROUND: Color = Color::rgba(0.0, 0.0, 0.0, 0.6);
 
 pub struct MenuPlugin;
 impl Plugin for MenuPlugin {
@@ -13,42 +15,155 @@ impl Plugin for MenuPlugin {
         app
             .add_state(MenuState::Disabled)
             .add_system_set(SystemSet::on_enter(GameState::Menu).with_system(menu_setup))
+            .add_system_set(SystemSet::on_enter(GameState::Paused).with_system(pause_setup))
+            .add_system_set(SystemSet::on_resume(GameState::Game).with_system(close_pause_menu))
+            .add_system_set(SystemSet::on_enter(MenuState::Paused).with_system(pause_menu_setup))
+            .add_system_set(SystemSet::on_exit(MenuState::Paused).with_system(despawn_entities::<OnPauseMenuScreen>))
             .add_system_set(SystemSet::on_enter(MenuState::Main).with_system(main_menu_setup))
             .add_system_set(SystemSet::on_exit(MenuState::Main).with_system(despawn_entities::<OnMainMenuScreen>))
+            .add_system_set(SystemSet::on_enter(MenuState::Leaderboard).with_system(leaderboard_menu_setup))
+            .add_system_set(SystemSet::on_exit(MenuState::Leaderboard).with_system(despawn_entities::<OnLeaderboardMenuScreen>))
+            .add_system_set(SystemSet::on_enter(MenuState::Settings).with_system(settings_menu_setup))
+            .add_system_set(SystemSet::on_exit(MenuState::Settings).with_system(despawn_entities::<OnSettingsMenuScreen>))
+            .add_system_set(SystemSet::on_enter(MenuState::SettingsDisplay).with_system(display_settings_menu_setup))
+            .add_system_set(SystemSet::on_exit(MenuState::SettingsDisplay).with_system(despawn_entities::<OnDisplaySettingsMenuScreen>))
+            .add_system_set(SystemSet::on_enter(MenuState::SettingsSound).with_system(sound_settings_menu_setup))
+            .add_system_set(SystemSet::on_exit(MenuState::SettingsSound).with_system(despawn_entities::<OnSoundSettingsMenuScreen>))
+            .add_system_set(SystemSet::on_enter(MenuState::SettingsGameplay).with_system(gameplay_settings_menu_setup))
+            .add_system_set(SystemSet::on_exit(MenuState::SettingsGameplay).with_system(despawn_entities::<OnGameplaySettingsMenuScreen>))
             // Common systems to all screens that handles buttons behaviour
             .add_system_set(
                 SystemSet::on_update(GameState::Menu)
                     .with_system(menu_action)
-                    .with_system(button_system),
+                    .with_system(button_system)
+                    .with_system(setting_button),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Paused)
+                    .with_system(menu_action)
+                    .with_system(button_system)
+                    .with_system(setting_button),
             );
     }
 }
 
+// `Escape` over a level. It needs no window, so it also runs under `MinimalPlugins`
+pub struct PausePlugin;
+impl Plugin for PausePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_system_set(SystemSet::on_update(GameState::Game).with_system(pause_keyboard))
+            .add_system_set(SystemSet::on_update(GameState::Paused).with_system(pause_keyboard));
+    }
+}
+
 // State used for the current menu screen
 #[derive(Clone, Eq, PartialEq, Debug, Hash)]
 enum MenuState {
     Main,
-    //Settings,
-    //SettingsDisplay,
-    //SettingsSound,
+    Leaderboard,
+    Settings,
+    SettingsDisplay,
+    SettingsSound,
+    SettingsGameplay,
+    // Over a level, the settings screens come back to it
+    Paused,
     Disabled,
 }
 
 #[derive(Component)]
 enum MenuButtonAction {
+    Continue,
     Play,
+    Leaderboard,
     Settings,
     SettingsDisplay,
     SettingsSound,
+    SettingsGameplay,
     BackToMainMenu,
     BackToSettings,
+    Resume,
+    RestartLevel,
+    BackToPauseMenu,
+    QuitToMenu,
     Quit,
 }
 
+// Value a settings button picks
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+enum Setting {
+    WindowMode(WindowModeSetting),
+    Resolution(u32, u32),
+    Vsync(bool),
+    Shadows(bool),
+    MasterVolume(u32),
+    MusicVolume(u32),
+    EffectsVolume(u32),
+    Difficulty(Difficulty),
+    Mode(PlayMode),
+}
+
+impl Setting {
+    fn is_current(self, settings: &Settings) -> bool {
+        match self {
+            Setting::WindowMode(mode) => settings.display.window_mode == mode,
+            Setting::Resolution(width, height) => settings.display.resolution == (width, height),
+            Setting::Vsync(vsync) => settings.display.vsync == vsync,
+            Setting::Shadows(shadows) => settings.display.shadows == shadows,
+            Setting::MasterVolume(volume) => settings.sound.master == volume,
+            Setting::MusicVolume(volume) => settings.sound.music == volume,
+            Setting::EffectsVolume(volume) => settings.sound.effects == volume,
+            Setting::Difficulty(difficulty) => settings.gameplay.difficulty == difficulty,
+            Setting::Mode(mode) => settings.gameplay.mode == mode,
+        }
+    }
+
+    fn apply(self, settings: &mut Settings) {
+        match self {
+            Setting::WindowMode(mode) => settings.display.window_mode = mode,
+            Setting::Resolution(width, height) => settings.display.resolution = (width, height),
+            Setting::Vsync(vsync) => settings.display.vsync = vsync,
+            Setting::Shadows(shadows) => settings.display.shadows = shadows,
+            Setting::MasterVolume(volume) => settings.sound.master = volume,
+            Setting::MusicVolume(volume) => settings.sound.music = volume,
+            Setting::EffectsVolume(volume) => settings.sound.effects = volume,
+            Setting::Difficulty(difficulty) => settings.gameplay.difficulty = difficulty,
+            Setting::Mode(mode) => settings.gameplay.mode = mode,
+        }
+    }
+
+    // Both buttons are on the same row, only one of them is selected
+    fn same_row(self, other: Setting) -> bool {
+        std::mem::discriminant(&self) == std::mem::discriminant(&other)
+    }
+}
+
+const RESOLUTIONS: [(u32, u32); 3] = [(1280, 720), (1600, 900), (1920, 1080)];
+const VOLUMES: [u32; 5] = [0, 25, 50, 75, 100];
+
 // Tag component used to tag entities added on the main menu screen
 #[derive(Component)]
 struct OnMainMenuScreen;
 
+#[derive(Component)]
+struct OnLeaderboardMenuScreen;
+
+#[derive(Component)]
+struct OnPauseMenuScreen;
+
+// Tag components of the settings screens
+#[derive(Component)]
+struct OnSettingsMenuScreen;
+
+#[derive(Component)]
+struct OnDisplaySettingsMenuScreen;
+
+#[derive(Component)]
+struct OnSoundSettingsMenuScreen;
+
+#[derive(Component)]
+struct OnGameplaySettingsMenuScreen;
+
 // Tag component used to mark wich setting is currently selected
 #[derive(Component)]
 struct SelectedOption;
@@ -57,24 +172,36 @@ fn menu_setup(mut menu_state: ResMut<State<MenuState>>) {
     menu_state.set(MenuState::Main);
 }
 
-fn main_menu_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
-    let font = asset_server.load(fonts::MAIN_FONT);
-    // Common style for all buttons on the screen
-    let button_style = Style {
-        size: Size::new(Val::Px(250.0), Val::Px(64.0)),
-        margin: Rect::all(Val::Px(20.0)),
-        justify_content: JustifyContent::Center,
-        align_items: AlignItems::Center,
-        ..Default::default()
-    };
-    let button_text_style = TextStyle {
-        font: font.clone(),
-        font_size: 40.0,
-        color: TEXT_COLOR,
+fn pause_setup(mut menu_state: ResMut<State<MenuState>>) {
+    menu_state.set(MenuState::Paused);
+}
+
+// `Escape` stops the level where it is, and carries on with it from any of the pause screens
+fn pause_keyboard(mut game_state: ResMut<State<GameState>>, mut keyboard_input: ResMut<Input<KeyCode>>) {
+    if !keyboard_input.just_pressed(KeyCode::Escape) {
+        return;
+    }
+    let changed = match game_state.current() {
+        GameState::Paused => game_state.pop(),
+        _ => game_state.push(GameState::Paused),
     };
+    // the state that comes in runs this frame too, it mustn't see the same press
+    if changed.is_ok() {
+        keyboard_input.reset(KeyCode::Escape);
+    }
+}
+
+fn close_pause_menu(mut menu_state: ResMut<State<MenuState>>) {
+    let _ = menu_state.set(MenuState::Disabled);
+}
 
-    let bg = Color::DARK_GRAY;
+// Full screen column on the menu background, every screen is built in one
+fn spawn_screen<T: Component>(commands: &mut Commands, tag: T, spawn_children: impl FnOnce(&mut ChildBuilder)) {
+    spawn_screen_over(commands, tag, Color::DARK_GRAY, spawn_children);
+}
 
+// Same on a background `bg`, see-through over the level
+fn spawn_screen_over<T: Component>(commands: &mut Commands, tag: T, bg: Color, spawn_children: impl FnOnce(&mut ChildBuilder)) {
     commands
         .spawn_bundle(NodeBundle {
             style: Style {
@@ -84,7 +211,7 @@ fn main_menu_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
             color: bg.into(),
             ..Default::default()
         })
-        .insert(OnMainMenuScreen)
+        .insert(tag)
         .with_children(|parent| {
             parent.spawn_bundle(NodeBundle {
                 style: Style {
@@ -93,61 +220,208 @@ fn main_menu_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
                     align_items: AlignItems::Center,
                     ..Default::default()
                 },
-                color: bg.into(),
+                color: Color::NONE.into(),
                 ..Default::default()
             })
-            .with_children(|parent| {
-                parent.spawn_bundle(TextBundle {
-                    style: Style {
-                        margin: Rect::all(Val::Px(50.0)),
-                        ..Default::default()
-                    },
+            .with_children(spawn_children);
+        });
+}
 
-                    text: Text::with_section(
-                        "ErrIO",
-                        TextStyle {
-                            font: font.clone(),
-                            font_size: 80.0,
-                            color: TEXT_COLOR,
-                        },
-                        Default::default()
-                    ),
-                    ..Default::default()
-                });
+fn button_text_style(asset_server: &AssetServer, font_size: f32) -> TextStyle {
+    TextStyle {
+        font: asset_server.load(fonts::MAIN_FONT),
+        font_size,
+        color: TEXT_COLOR,
+    }
+}
 
-                // Display Menu
-                // - New Game
-                // - Quit
+fn spawn_button(parent: &mut ChildBuilder, asset_server: &AssetServer, label: &str, action: MenuButtonAction) {
+    parent.spawn_bundle(ButtonBundle {
+        style: Style {
+            size: Size::new(Val::Px(250.0), Val::Px(64.0)),
+            margin: Rect::all(Val::Px(20.0)),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..Default::default()
+        },
+        color: NORMAL_BUTTON.into(),
+        ..Default::default()
+    })
+    .insert(action)
+    .with_children(|parent| {
+        parent.spawn_bundle(TextBundle {
+            text: Text::with_section(label, button_text_style(asset_server, 40.0), Default::default()),
+            ..Default::default()
+        });
+    });
+}
 
-                // New Game
-                parent.spawn_bundle(ButtonBundle {
-                    style: button_style.clone(),
-                    color: NORMAL_BUTTON.into(),
+// A label followed by one button per value, the current one selected
+fn spawn_setting_row(parent: &mut ChildBuilder, asset_server: &AssetServer, settings: &Settings, label: &str, options: &[(Setting, String)]) {
+    parent.spawn_bundle(NodeBundle {
+        style: Style {
+            align_items: AlignItems::Center,
+            ..Default::default()
+        },
+        color: Color::DARK_GRAY.into(),
+        ..Default::default()
+    })
+    .with_children(|parent| {
+        parent.spawn_bundle(TextBundle {
+            style: Style {
+                size: Size::new(Val::Px(200.0), Val::Auto),
+                ..Default::default()
+            },
+            text: Text::with_section(label, button_text_style(asset_server, 30.0), Default::default()),
+            ..Default::default()
+        });
+        for (setting, text) in options {
+            let mut entity = parent.spawn_bundle(ButtonBundle {
+                style: Style {
+                    size: Size::new(Val::Px(170.0), Val::Px(50.0)),
+                    margin: Rect::all(Val::Px(10.0)),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
                     ..Default::default()
-                })
-                .insert(MenuButtonAction::Play)
-                .with_children(|parent| {
-                    parent.spawn_bundle(TextBundle {
-                        text: Text::with_section("New Game", button_text_style.clone(), Default::default()),
-                        ..Default::default()
-                    });
-                });
-
-                // Quit
-                parent.spawn_bundle(ButtonBundle {
-                    style: button_style.clone(),
-                    color: NORMAL_BUTTON.into(),
+                },
+                color: NORMAL_BUTTON.into(),
+                ..Default::default()
+            });
+            entity.insert(*setting).with_children(|parent| {
+                parent.spawn_bundle(TextBundle {
+                    text: Text::with_section(text.as_str(), button_text_style(asset_server, 26.0), Default::default()),
                     ..Default::default()
-                })
-                .insert(MenuButtonAction::Quit)
-                .with_children(|parent| {
-                    parent.spawn_bundle(TextBundle {
-                        text: Text::with_section("Quit", button_text_style.clone(), Default::default()),
-                        ..Default::default()
-                    });
                 });
             });
+            if setting.is_current(settings) {
+                entity.insert(SelectedOption);
+            }
+        }
+    });
+}
+
+fn on_off(setting: fn(bool) -> Setting) -> Vec<(Setting, String)> {
+    vec![(setting(true), "On".to_string()), (setting(false), "Off".to_string())]
+}
+
+fn volumes(setting: fn(u32) -> Setting) -> Vec<(Setting, String)> {
+    VOLUMES.iter().map(|&volume| (setting(volume), format!("{}%", volume))).collect()
+}
+
+fn main_menu_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    spawn_screen(&mut commands, OnMainMenuScreen, |parent| {
+        parent.spawn_bundle(TextBundle {
+            style: Style {
+                margin: Rect::all(Val::Px(50.0)),
+                ..Default::default()
+            },
+
+            text: Text::with_section("ErrIO", button_text_style(&asset_server, 80.0), Default::default()),
+            ..Default::default()
+        });
+
+        // Display Menu
+        // - New Game
+        // - Leaderboard
+        // - Settings
+        // - Quit
+        if save::save_exists() {
+            spawn_button(parent, &asset_server, "Continue", MenuButtonAction::Continue);
+        }
+        spawn_button(parent, &asset_server, "New Game", MenuButtonAction::Play);
+        spawn_button(parent, &asset_server, "Leaderboard", MenuButtonAction::Leaderboard);
+        spawn_button(parent, &asset_server, "Settings", MenuButtonAction::Settings);
+        spawn_button(parent, &asset_server, "Quit", MenuButtonAction::Quit);
+    });
+}
+
+fn leaderboard_menu_setup(mut commands: Commands, asset_server: Res<AssetServer>, leaderboard: Res<Leaderboard>) {
+    spawn_screen(&mut commands, OnLeaderboardMenuScreen, |parent| {
+        parent.spawn_bundle(TextBundle {
+            style: Style {
+                margin: Rect::all(Val::Px(30.0)),
+                ..Default::default()
+            },
+            text: Text::with_section("Leaderboard", button_text_style(&asset_server, 60.0), Default::default()),
+            ..Default::default()
+        });
+        parent.spawn_bundle(TextBundle {
+            text: Text::with_section(leaderboard.table(None), button_text_style(&asset_server, 24.0), Default::default()),
+            ..Default::default()
+        });
+        spawn_button(parent, &asset_server, "Back", MenuButtonAction::BackToMainMenu);
+    });
+}
+
+fn pause_menu_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    spawn_screen_over(&mut commands, OnPauseMenuScreen, PAUSE_BACKGROUND, |parent| {
+        parent.spawn_bundle(TextBundle {
+            style: Style {
+                margin: Rect::all(Val::Px(50.0)),
+                ..Default::default()
+            },
+            text: Text::with_section("Paused", button_text_style(&asset_server, 80.0), Default::default()),
+            ..Default::default()
         });
+        spawn_button(parent, &asset_server, "Resume", MenuButtonAction::Resume);
+        spawn_button(parent, &asset_server, "Restart Level", MenuButtonAction::RestartLevel);
+        spawn_button(parent, &asset_server, "Settings", MenuButtonAction::Settings);
+        spawn_button(parent, &asset_server, "Quit to Menu", MenuButtonAction::QuitToMenu);
+    });
+}
+
+fn settings_menu_setup(mut commands: Commands, asset_server: Res<AssetServer>, game_state: Res<State<GameState>>) {
+    // opened from the pause menu, back goes there
+    let back = match game_state.current() {
+        GameState::Paused => MenuButtonAction::BackToPauseMenu,
+        _ => MenuButtonAction::BackToMainMenu,
+    };
+    spawn_screen(&mut commands, OnSettingsMenuScreen, |parent| {
+        spawn_button(parent, &asset_server, "Display", MenuButtonAction::SettingsDisplay);
+        spawn_button(parent, &asset_server, "Sound", MenuButtonAction::SettingsSound);
+        spawn_button(parent, &asset_server, "Gameplay", MenuButtonAction::SettingsGameplay);
+        spawn_button(parent, &asset_server, "Back", back);
+    });
+}
+
+fn display_settings_menu_setup(mut commands: Commands, asset_server: Res<AssetServer>, settings: Res<Settings>) {
+    spawn_screen(&mut commands, OnDisplaySettingsMenuScreen, |parent| {
+        spawn_setting_row(parent, &asset_server, &settings, "Window", &[
+            (Setting::WindowMode(WindowModeSetting::Windowed), "Windowed".to_string()),
+            (Setting::WindowMode(WindowModeSetting::Fullscreen), "Fullscreen".to_string()),
+            (Setting::WindowMode(WindowModeSetting::Borderless), "Borderless".to_string()),
+        ]);
+        let resolutions: Vec<(Setting, String)> = RESOLUTIONS.iter()
+            .map(|&(width, height)| (Setting::Resolution(width, height), format!("{}x{}", width, height)))
+            .collect();
+        spawn_setting_row(parent, &asset_server, &settings, "Resolution", &resolutions);
+        spawn_setting_row(parent, &asset_server, &settings, "VSync", &on_off(Setting::Vsync));
+        spawn_setting_row(parent, &asset_server, &settings, "Shadows", &on_off(Setting::Shadows));
+        spawn_button(parent, &asset_server, "Back", MenuButtonAction::BackToSettings);
+    });
+}
+
+fn sound_settings_menu_setup(mut commands: Commands, asset_server: Res<AssetServer>, settings: Res<Settings>) {
+    spawn_screen(&mut commands, OnSoundSettingsMenuScreen, |parent| {
+        spawn_setting_row(parent, &asset_server, &settings, "Master", &volumes(Setting::MasterVolume));
+        spawn_setting_row(parent, &asset_server, &settings, "Music", &volumes(Setting::MusicVolume));
+        spawn_setting_row(parent, &asset_server, &settings, "Effects", &volumes(Setting::EffectsVolume));
+        spawn_button(parent, &asset_server, "Back", MenuButtonAction::BackToSettings);
+    });
+}
+
+fn gameplay_settings_menu_setup(mut commands: Commands, asset_server: Res<AssetServer>, settings: Res<Settings>) {
+    spawn_screen(&mut commands, OnGameplaySettingsMenuScreen, |parent| {
+        let difficulties: Vec<(Setting, String)> = Difficulty::ALL.iter()
+            .map(|&difficulty| (Setting::Difficulty(difficulty), difficulty.name().to_string()))
+            .collect();
+        spawn_setting_row(parent, &asset_server, &settings, "Difficulty", &difficulties);
+        spawn_setting_row(parent, &asset_server, &settings, "Guards", &[
+            (Setting::Mode(PlayMode::TurnBased), "Turn-based".to_string()),
+            (Setting::Mode(PlayMode::RealTime), "Real time".to_string()),
+        ]);
+        spawn_button(parent, &asset_server, "Back", MenuButtonAction::BackToSettings);
+    });
 }
 
 fn menu_action(
@@ -155,21 +429,74 @@ fn menu_action(
     mut app_exit_events: EventWriter<AppExit>,
     mut menu_state: ResMut<State<MenuState>>,
     mut game_state: ResMut<State<GameState>>,
+    mut restore: ResMut<Restore>,
+    floor_start: Res<FloorStart>,
 ) {
     for (interaction, menu_button_action) in interaction_query.iter() {
         if *interaction == Interaction::Clicked {
             match menu_button_action {
+                MenuButtonAction::Continue => match RunSnapshot::load(RunSnapshot::path()) {
+                    Ok(run) => {
+                        *restore = run.into();
+                        game_state.set(GameState::Game).unwrap();
+                        menu_state.set(MenuState::Disabled).unwrap();
+                    },
+                    Err(err) => error!("failed to load {}: {}", RunSnapshot::path().display(), err),
+                },
                 MenuButtonAction::Play => {
                     game_state.set(GameState::Game).unwrap();
                     menu_state.set(MenuState::Disabled).unwrap();
                 },
+                MenuButtonAction::Leaderboard => menu_state.set(MenuState::Leaderboard).unwrap(),
+                MenuButtonAction::Settings => menu_state.set(MenuState::Settings).unwrap(),
+                MenuButtonAction::SettingsDisplay => menu_state.set(MenuState::SettingsDisplay).unwrap(),
+                MenuButtonAction::SettingsSound => menu_state.set(MenuState::SettingsSound).unwrap(),
+                MenuButtonAction::SettingsGameplay => menu_state.set(MenuState::SettingsGameplay).unwrap(),
+                MenuButtonAction::BackToMainMenu => menu_state.set(MenuState::Main).unwrap(),
+                MenuButtonAction::BackToSettings => menu_state.set(MenuState::Settings).unwrap(),
+                // `Escape` may have resumed already
+                MenuButtonAction::Resume => {
+                    let _ = game_state.pop();
+                },
+                // nothing to go back to before the first step, carry on instead
+                MenuButtonAction::RestartLevel => if floor_start.restart(&mut restore, &mut game_state) || game_state.pop().is_ok() {
+                    let _ = menu_state.set(MenuState::Disabled);
+                },
+                MenuButtonAction::BackToPauseMenu => menu_state.set(MenuState::Paused).unwrap(),
+                MenuButtonAction::QuitToMenu => {
+                    game_state.replace(GameState::Menu).unwrap();
+                    menu_state.set(MenuState::Main).unwrap();
+                },
                 MenuButtonAction::Quit => app_exit_events.send(AppExit),
-                _ => {}
             }
         }
     }
 }
 
+// A settings button becomes the selected one of its row, the new value is applied and saved
+fn setting_button(
+    interaction_query: Query<(&Interaction, &Setting, Entity), (Changed<Interaction>, With<Button>)>,
+    mut selected_query: Query<(Entity, &Setting, &mut UiColor), With<SelectedOption>>,
+    mut commands: Commands,
+    mut settings: ResMut<Settings>,
+    mut changes: EventWriter<SettingsChanged>,
+) {
+    for (interaction, button_setting, entity) in interaction_query.iter() {
+        if *interaction != Interaction::Clicked || button_setting.is_current(&settings) {
+            continue;
+        }
+        for (previous_button, previous_setting, mut previous_color) in selected_query.iter_mut() {
+            if previous_setting.same_row(*button_setting) {
+                *previous_color = NORMAL_BUTTON.into();
+                commands.entity(previous_button).remove::<SelectedOption>();
+            }
+        }
+        commands.entity(entity).insert(SelectedOption);
+        button_setting.apply(&mut settings);
+        changes.send(SettingsChanged);
+    }
+}
+
 // This system handles changing all buttons color based on mouse interaction
 fn button_system(
     mut interaction_query: Query<
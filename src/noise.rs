@@ -0,0 +1,38 @@
+use crate::{Cell, DoorState, grid::{Grid, Direction}, pathfinding::{CostModel, DistanceMap, Pathfinder, DIST_BETWEEN}};
+
+// How far, in steps, the player's noises carry
+pub const RUN_NOISE: i32 = 4;
+pub const DOOR_NOISE: i32 = 3;
+pub const TAKEDOWN_NOISE: i32 = 3;
+// Steps of loudness a doorway swallows
+const DOOR_DAMPING: i32 = 2;
+
+// A sound made at `at`, heard by anyone within `radius` steps of it around walls
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Noise {
+    pub at: (usize, usize),
+    pub radius: i32,
+}
+
+// Sound travels through every open side, doorways muffle it and shut doors even more
+pub struct NoiseCost;
+
+impl CostModel for NoiseCost {
+    fn step_cost(&self, from: &Cell, to: &Cell, dir: Direction) -> Option<i32> {
+        Some(match from.door(dir).or_else(|| to.door(dir.opposite())) {
+            None => DIST_BETWEEN,
+            Some(DoorState::Open) => DIST_BETWEEN * (1 + DOOR_DAMPING),
+            Some(_) => DIST_BETWEEN * (1 + 2 * DOOR_DAMPING),
+        })
+    }
+}
+
+// Cells the noise reaches, with how much of its radius it used up on the way
+pub fn noise_reach(grid: &Grid, noise: &Noise) -> DistanceMap {
+    Pathfinder::new(&NoiseCost).distance_map_within(grid, &[noise.at], Some(noise.radius * DIST_BETWEEN))
+}
+
+// Whether someone standing on (i, j) hears the noise
+pub fn hears(grid: &Grid, noise: &Noise, i: usize, j: usize) -> bool {
+    noise_reach(grid, noise).is_reachable(i, j)
+}
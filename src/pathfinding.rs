@@ -1,100 +1,520 @@
-use crate::{ game::Game, Cell, consts::{MAP_SIZE_I, MAP_SIZE_J}};
+use std::{cmp::Ordering, collections::{BinaryHeap, HashMap}};
+use serde::{Serialize, Deserialize};
+use crate::{Cell, Terrain, DoorState, grid::{Grid, Direction}};
 
-#[derive(Debug, PartialEq, Clone)]
-struct PathNode {
-    cell: Cell,
-    g_cost: i32,
-    h_cost: i32,
+// Entry of the open set. Ordered so the `BinaryHeap` pops the lowest f-cost first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct OpenNode {
     f_cost: i32,
-    came_from: Box<Option<PathNode>>,
-}
-
-const DIRECTIONAL_ARRAY_X: [i32; 4] = [ 0, 1, 0,-1];
-const DIRECTIONAL_ARRAY_Y: [i32; 4] = [-1, 0, 1, 0];
-const DIST_BETWEEN: i32 = 10;
-
-fn heuristic(a: &Cell, b: &Cell) -> i32 {
-    let diff_x: i32 = (a.i as i32 - b.i as i32).abs();
-    let diff_y: i32 = (a.j as i32 - b.j as i32).abs();
-    //diff_x.min(diff_y) * DIST_BETWEEN + (diff_x - diff_y).abs() * (DIST_BETWEEN as f32 * DIST_BETWEEN as f32).sqrt() as i32 // Diagonal movement
-    diff_x + diff_y
-}
-
-pub fn find_path(game: &Game, start_cell: &Cell, end_cell: &Cell) -> Option<Vec<Cell>> {
-    let start_node: PathNode = PathNode {
-        cell: start_cell.clone(),
-        g_cost: 0,
-        h_cost: 0,
-        f_cost: 0,
-        came_from: Box::new(None),
-    };
-    let end_node: PathNode = PathNode {
-        cell: end_cell.clone(),
-        g_cost: 0,
-        h_cost: 0,
-        f_cost: 0,
-        came_from: Box::new(None),
-    };
-
-    let mut open_set: Vec<PathNode> = Vec::new();
-    let mut closed_set: Vec<PathNode> = Vec::new();
-
-    open_set.push(start_node);
-
-    while open_set.len() > 0 {
-        let mut lowest_index = 0;
-        for i in 0..open_set.len() {
-            if open_set[i].f_cost < open_set[lowest_index].f_cost {
-                lowest_index = i;
+    g_cost: i32,
+    index: usize,
+}
+
+impl Ord for OpenNode {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.f_cost.cmp(&self.f_cost)
+            // on ties prefer the node furthest along, it is closer to the goal
+            .then_with(|| self.g_cost.cmp(&other.g_cost))
+            .then_with(|| other.index.cmp(&self.index))
+    }
+}
+
+impl PartialOrd for OpenNode {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+pub const DIST_BETWEEN: i32 = 10;
+const DIAGONAL_DIST: i32 = 14; // DIST_BETWEEN * √2
+
+// Price of stepping from a cell into its neighbour in `dir`, each agent plans with its own model.
+// `None` makes the step impossible for that agent.
+pub trait CostModel {
+    fn step_cost(&self, from: &Cell, to: &Cell, dir: Direction) -> Option<i32>;
+
+    // Diagonal steps cost √2 of the orthogonal step into the same cell, `via` is one of the two sides crossed
+    fn diagonal_cost(&self, from: &Cell, to: &Cell, via: Direction) -> Option<i32> {
+        self.step_cost(from, to, via).map(|cost| cost * DIAGONAL_DIST / DIST_BETWEEN)
+    }
+
+    // Cheapest orthogonal step the model can produce, the heuristic is scaled by it so it never overestimates
+    fn min_step_cost(&self) -> i32 {
+        DIST_BETWEEN
+    }
+}
+
+// Every step costs the same, routes are plain shortest paths
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct UniformCost;
+
+impl CostModel for UniformCost {
+    fn step_cost(&self, _from: &Cell, _to: &Cell, _dir: Direction) -> Option<i32> {
+        Some(DIST_BETWEEN)
+    }
+}
+
+// Weighs terrain, doors and elevation on top of the distance
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TerrainCost {
+    // Cost of entering a cell of each terrain, `None` when the agent can't go there
+    pub floor: Option<i32>,
+    pub shadow: Option<i32>,
+    pub vent: Option<i32>,
+    pub hazard: Option<i32>,
+    // Added when walking through a doorway
+    pub door: i32,
+    // Added when the door has to be opened first, and when it has to be unlocked.
+    // `None` when the agent can't
+    pub open_door: Option<i32>,
+    pub unlock: Option<i32>,
+    // Added per unit of height difference between the two cells
+    pub climb: f32,
+}
+
+impl Default for TerrainCost {
+    fn default() -> Self {
+        Self {
+            floor: Some(DIST_BETWEEN),
+            shadow: Some(DIST_BETWEEN),
+            vent: Some(DIST_BETWEEN * 2),
+            hazard: Some(DIST_BETWEEN * 4),
+            door: DIST_BETWEEN / 2,
+            open_door: Some(DIST_BETWEEN),
+            unlock: None,
+            climb: 100.0,
+        }
+    }
+}
+
+impl TerrainCost {
+    // Guards stay out of the vents, don't mind shadows and carry keycards
+    pub fn guard() -> Self {
+        Self {
+            vent: None,
+            unlock: Some(DIST_BETWEEN),
+            ..Default::default()
+        }
+    }
+
+    // The player sticks to the shadows and squeezes through vents when it saves time
+    pub fn player() -> Self {
+        Self {
+            shadow: Some(DIST_BETWEEN * 6 / 10),
+            vent: Some(DIST_BETWEEN * 3 / 2),
+            ..Default::default()
+        }
+    }
+}
+
+impl CostModel for TerrainCost {
+    fn step_cost(&self, from: &Cell, to: &Cell, dir: Direction) -> Option<i32> {
+        let enter = match to.terrain {
+            Terrain::Floor => self.floor,
+            Terrain::Shadow => self.shadow,
+            Terrain::Vent => self.vent,
+            Terrain::Hazard => self.hazard,
+        }?;
+        let door = match from.door(dir) {
+            None => 0,
+            Some(DoorState::Open) => self.door,
+            Some(DoorState::Closed) => self.door + self.open_door?,
+            Some(DoorState::Locked) => self.door + self.open_door? + self.unlock?,
+        };
+        let climb = ((to.height - from.height).abs() * self.climb).round() as i32;
+        Some(enter + door + climb)
+    }
+
+    fn min_step_cost(&self) -> i32 {
+        [self.floor, self.shadow, self.vent, self.hazard].iter()
+            .flatten()
+            .copied()
+            .min()
+            .unwrap_or(DIST_BETWEEN)
+    }
+}
+
+// Estimate of the remaining cost to the goal
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Heuristic {
+    // Exact on open ground with orthogonal moves only, overestimates once diagonals are allowed
+    Manhattan,
+    // Counts a diagonal step like an orthogonal one, admissible but loose with diagonals
+    Chebyshev,
+    // Exact on open ground with diagonal moves
+    Octile,
+    // No estimate, the search becomes Dijkstra
+    Zero,
+}
+
+impl Default for Heuristic {
+    fn default() -> Self {
+        Heuristic::Manhattan
+    }
+}
+
+impl Heuristic {
+    // Estimate between two cells when the cheapest orthogonal step costs `step`
+    pub fn estimate(self, a: &Cell, b: &Cell, step: i32) -> i32 {
+        let diff_x: i32 = (a.i as i32 - b.i as i32).abs();
+        let diff_y: i32 = (a.j as i32 - b.j as i32).abs();
+        match self {
+            Heuristic::Manhattan => (diff_x + diff_y) * step,
+            Heuristic::Chebyshev => diff_x.max(diff_y) * step,
+            Heuristic::Octile => {
+                let diagonal_step = step * DIAGONAL_DIST / DIST_BETWEEN;
+                diff_x.min(diff_y) * diagonal_step + (diff_x - diff_y).abs() * step
+            },
+            Heuristic::Zero => 0,
+        }
+    }
+}
+
+// A* search over a grid with a given cost model, heuristic and move set
+#[derive(Clone, Copy)]
+pub struct Pathfinder<'a> {
+    pub cost: &'a dyn CostModel,
+    pub heuristic: Heuristic,
+    pub diagonal: bool,
+}
+
+impl<'a> Pathfinder<'a> {
+    pub fn new(cost: &'a dyn CostModel) -> Self {
+        Self {
+            cost,
+            heuristic: Heuristic::default(),
+            diagonal: false,
+        }
+    }
+
+    pub fn with_heuristic(mut self, heuristic: Heuristic) -> Self {
+        self.heuristic = heuristic;
+        self
+    }
+
+    // Allow diagonal steps, they are only taken when both ways around the corner are open
+    pub fn with_diagonal(mut self, diagonal: bool) -> Self {
+        self.diagonal = diagonal;
+        self
+    }
+
+    pub fn find_path(&self, grid: &Grid, start_cell: &Cell, end_cell: &Cell) -> Option<Vec<Cell>> {
+        self.find_path_with_cost(grid, start_cell, end_cell).map(|(path, _)| path)
+    }
+
+    // Path like `find_path` along with its total cost
+    pub fn find_path_with_cost(&self, grid: &Grid, start_cell: &Cell, end_cell: &Cell) -> Option<(Vec<Cell>, i32)> {
+        let start = grid.index(start_cell.i, start_cell.j)?;
+        let end = grid.index(end_cell.i, end_cell.j)?;
+        let min_step = self.cost.min_step_cost();
+
+        // Search state lives in flat arrays indexed like the grid
+        let mut g_cost: Vec<i32> = vec![i32::MAX; grid.len()];
+        let mut came_from: Vec<Option<usize>> = vec![None; grid.len()];
+        let mut closed: Vec<bool> = vec![false; grid.len()];
+        let mut open_set: BinaryHeap<OpenNode> = BinaryHeap::new();
+
+        g_cost[start] = 0;
+        open_set.push(OpenNode { f_cost: self.heuristic.estimate(&grid[start], &grid[end], min_step), g_cost: 0, index: start });
+
+        while let Some(current) = open_set.pop() {
+            // Nodes are never updated in place, stale copies are skipped here instead
+            if closed[current.index] {
+                continue;
             }
+            if current.index == end {
+                let mut path: Vec<Cell> = vec![grid[end]];
+                let mut index = end;
+                while let Some(previous) = came_from[index] {
+                    path.push(grid[previous]);
+                    index = previous;
+                }
+                return Some((path, current.g_cost));
+            }
+            closed[current.index] = true;
+
+            self.for_each_step(grid, &grid[current.index], |next, neighbor, step| {
+                if closed[neighbor] {
+                    return;
+                }
+                let tentative_g_score = current.g_cost + step;
+                if tentative_g_score < g_cost[neighbor] {
+                    g_cost[neighbor] = tentative_g_score;
+                    came_from[neighbor] = Some(current.index);
+                    open_set.push(OpenNode {
+                        f_cost: tentative_g_score + self.heuristic.estimate(next, &grid[end], min_step),
+                        g_cost: tentative_g_score,
+                        index: neighbor,
+                    });
+                }
+            });
         }
-        let current: PathNode = open_set.remove(lowest_index);
+        return None;
+    }
+
+    // Space-time A* for cooperative planning. Each step of the path takes one tick, starting on `start_cell`
+    // at `start_tick`, and never enters a cell another agent reserved for that tick nor swaps places with one.
+    // Waiting in place is allowed, so the path may repeat a cell. Laid out like `find_path`.
+    pub fn find_path_timed(
+        &self,
+        grid: &Grid,
+        start_cell: &Cell,
+        end_cell: &Cell,
+        start_tick: u32,
+        reservations: &ReservationTable,
+        agent: usize,
+    ) -> Option<Vec<Cell>> {
+        let start = grid.index(start_cell.i, start_cell.j)?;
+        let end = grid.index(end_cell.i, end_cell.j)?;
+        let min_step = self.cost.min_step_cost();
+        // How far ahead to plan before giving up, leaves room to wait for others on the way
+        let horizon = ((grid.width() + grid.length()) * 2) as u32 + TIME_SLACK;
+
+        // States are (cell, ticks since the start), the time dimension makes flat arrays too big
+        let mut g_cost: HashMap<(usize, u32), i32> = HashMap::new();
+        let mut came_from: HashMap<(usize, u32), (usize, u32)> = HashMap::new();
+        let mut open_set: BinaryHeap<(OpenNode, u32)> = BinaryHeap::new();
 
-        if current.cell == end_node.cell {
-            let mut path: Vec<Cell> = Vec::new();
-            let mut path_node: &PathNode = &current;
-            path.push(current.cell.clone());
-            while let Some(previous) = &*path_node.came_from {
-                path.push(previous.cell.clone());
-                path_node = previous;
+        g_cost.insert((start, 0), 0);
+        open_set.push((OpenNode { f_cost: self.heuristic.estimate(&grid[start], &grid[end], min_step), g_cost: 0, index: start }, 0));
+
+        while let Some((current, time)) = open_set.pop() {
+            if g_cost.get(&(current.index, time)).map_or(false, |&cost| cost < current.g_cost) {
+                continue;
+            }
+            if current.index == end {
+                let mut path: Vec<Cell> = vec![grid[end]];
+                let mut state = (end, time);
+                while let Some(&previous) = came_from.get(&state) {
+                    path.push(grid[previous.0]);
+                    state = previous;
+                }
+                return Some(path);
+            }
+            if time >= horizon {
+                continue;
+            }
+
+            let cell = &grid[current.index];
+            let tick = start_tick + time;
+            let mut moves: Vec<(&Cell, usize, i32)> = vec![(cell, current.index, min_step)]; // waiting costs like the cheapest step
+            self.for_each_step(grid, cell, |next, index, step| moves.push((next, index, step)));
+            for (next, index, step) in moves {
+                if !reservations.is_free(next.i, next.j, tick + 1, agent)
+                    || reservations.is_swap(cell, next, tick, agent) {
+                    continue;
+                }
+                let neighbor = (index, time + 1);
+                let tentative_g_score = current.g_cost + step;
+                if g_cost.get(&neighbor).map_or(true, |&cost| tentative_g_score < cost) {
+                    g_cost.insert(neighbor, tentative_g_score);
+                    came_from.insert(neighbor, (current.index, time));
+                    open_set.push((OpenNode {
+                        f_cost: tentative_g_score + self.heuristic.estimate(next, &grid[end], min_step),
+                        g_cost: tentative_g_score,
+                        index: neighbor.0,
+                    }, neighbor.1));
+                }
             }
-            return Some(path);
         }
+        return None;
+    }
+
+    // Dijkstra flood from every source at once, giving the cost of reaching each cell from the closest one
+    pub fn distance_map(&self, grid: &Grid, sources: &[(usize, usize)]) -> DistanceMap {
+        self.distance_map_within(grid, sources, None)
+    }
 
-        // Find neighbors
-        for i in 0..4 {
-            let x = current.cell.i as i32 + DIRECTIONAL_ARRAY_X[i];
-            let y = current.cell.j as i32 + DIRECTIONAL_ARRAY_Y[i];
+    // Same as `distance_map`, cells costing more than `max_cost` are left out
+    pub fn distance_map_within(&self, grid: &Grid, sources: &[(usize, usize)], max_cost: Option<i32>) -> DistanceMap {
+        let mut costs: Vec<Option<i32>> = vec![None; grid.len()];
+        let mut came_from: Vec<Option<usize>> = vec![None; grid.len()];
+        let mut closed: Vec<bool> = vec![false; grid.len()];
+        let mut open_set: BinaryHeap<OpenNode> = BinaryHeap::new();
 
-            if x < 0
-               || x >= MAP_SIZE_I as i32
-               || y < 0
-               || y >= MAP_SIZE_J as i32
-               || closed_set.iter().any(|node| node.cell == game.map[y as usize * MAP_SIZE_I + x as usize])
-               || !game.map[current.cell.j as usize * MAP_SIZE_I + current.cell.i as usize].open_sides[i]
-               {
+        for index in sources.iter().filter_map(|&(i, j)| grid.index(i, j)) {
+            costs[index] = Some(0);
+            open_set.push(OpenNode { f_cost: 0, g_cost: 0, index });
+        }
+
+        while let Some(current) = open_set.pop() {
+            if closed[current.index] {
                 continue;
             }
+            closed[current.index] = true;
 
-            let tentative_g_score = current.g_cost + DIST_BETWEEN; // Orthogonal movement
+            self.for_each_step(grid, &grid[current.index], |_, neighbor, step| {
+                let tentative_g_score = current.g_cost + step;
+                if closed[neighbor] || max_cost.map_or(false, |max| tentative_g_score > max) {
+                    return;
+                }
+                if costs[neighbor].map_or(true, |cost| tentative_g_score < cost) {
+                    costs[neighbor] = Some(tentative_g_score);
+                    came_from[neighbor] = Some(current.index);
+                    open_set.push(OpenNode { f_cost: tentative_g_score, g_cost: tentative_g_score, index: neighbor });
+                }
+            });
+        }
+        DistanceMap { width: grid.width(), length: grid.length(), costs, came_from }
+    }
 
-            if let Some(neighbor) = open_set.iter().find(|&node| node.cell == game.map[y as usize * MAP_SIZE_I + x as usize]) {
-                if neighbor.g_cost <= tentative_g_score {
-                    continue;
+    // Calls `visit` with every cell reachable in one step from `cell`, its index in the grid and the cost of getting there
+    fn for_each_step<'g>(&self, grid: &'g Grid, cell: &Cell, mut visit: impl FnMut(&'g Cell, usize, i32)) {
+        // shut doors are up to the cost model
+        for (dir, next) in grid.passages(cell.i, cell.j) {
+            if let (Some(index), Some(step)) = (grid.index(next.i, next.j), self.cost.step_cost(cell, next, dir)) {
+                visit(next, index, step);
+            }
+        }
+        if !self.diagonal {
+            return;
+        }
+        for side in 0..4 {
+            let (a, b) = (Direction::from_index(side), Direction::from_index(side + 1));
+            if let Some(next) = diagonal_neighbor(grid, cell, a, b) {
+                if let (Some(index), Some(step)) = (grid.index(next.i, next.j), self.cost.diagonal_cost(cell, next, a)) {
+                    visit(next, index, step);
                 }
-            } else {
-                let h_score = heuristic(&game.map[y as usize * MAP_SIZE_I + x as usize], &end_node.cell);
-                let neighbor: PathNode = PathNode {
-                    cell: game.map[y as usize * MAP_SIZE_I + x as usize].clone(),
-                    g_cost: tentative_g_score,
-                    h_cost: h_score,
-                    f_cost: tentative_g_score + h_score,
-                    came_from: Box::new(Some(current.clone())),
-                };
-                open_set.push(neighbor);
             }
         }
-        closed_set.push(current);
     }
-    return None;
+}
+
+// Cost of reaching every cell of a grid from the nearest of a set of sources.
+// Built by `Pathfinder::distance_map`, it answers range and "how far" questions without searching again.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DistanceMap {
+    width: usize,
+    length: usize,
+    costs: Vec<Option<i32>>,
+    came_from: Vec<Option<usize>>,
+}
+
+impl DistanceMap {
+    // Cost from the nearest source, `None` when the cell can't be reached
+    pub fn cost(&self, i: usize, j: usize) -> Option<i32> {
+        if i < self.width && j < self.length {
+            self.costs[j * self.width + i]
+        } else {
+            None
+        }
+    }
+
+    pub fn is_reachable(&self, i: usize, j: usize) -> bool {
+        self.cost(i, j).is_some()
+    }
+
+    // Every reachable cell with its cost, row by row
+    pub fn reachable(&self) -> impl Iterator<Item = ((usize, usize), i32)> + '_ {
+        let width = self.width;
+        self.costs.iter().enumerate().filter_map(move |(index, cost)| {
+            cost.map(|cost| ((index % width, index / width), cost))
+        })
+    }
+
+    // Reachable cells whose cost is at most `max_cost`
+    pub fn within(&self, max_cost: i32) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.reachable().filter(move |&(_, cost)| cost <= max_cost).map(|(cell, _)| cell)
+    }
+
+    // Cheapest path from the nearest source to (i, j), laid out like `find_path`: goal first, source last
+    pub fn path_to(&self, grid: &Grid, i: usize, j: usize) -> Option<Vec<Cell>> {
+        self.cost(i, j)?;
+        let mut index = j * self.width + i;
+        let mut path: Vec<Cell> = vec![grid[index]];
+        while let Some(previous) = self.came_from[index] {
+            path.push(grid[previous]);
+            index = previous;
+        }
+        Some(path)
+    }
+}
+
+// Extra ticks a timed search may spend waiting on top of walking around the whole map
+const TIME_SLACK: u32 = 16;
+
+// Which agent holds which cell at which tick, so agents planning one after another keep out of each other's way
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReservationTable {
+    cells: HashMap<(usize, usize, u32), usize>,
+}
+
+impl ReservationTable {
+    pub fn holder(&self, i: usize, j: usize, tick: u32) -> Option<usize> {
+        self.cells.get(&(i, j, tick)).copied()
+    }
+
+    // Free, or already held by `agent`
+    pub fn is_free(&self, i: usize, j: usize, tick: u32, agent: usize) -> bool {
+        self.holder(i, j, tick).map_or(true, |holder| holder == agent)
+    }
+
+    // Whether stepping `from` -> `to` between `tick` and `tick + 1` crosses another agent coming the other way
+    pub fn is_swap(&self, from: &Cell, to: &Cell, tick: u32, agent: usize) -> bool {
+        match (self.holder(to.i, to.j, tick), self.holder(from.i, from.j, tick + 1)) {
+            (Some(a), Some(b)) => a == b && a != agent,
+            _ => false,
+        }
+    }
+
+    pub fn reserve(&mut self, i: usize, j: usize, tick: u32, agent: usize) {
+        self.cells.insert((i, j, tick), agent);
+    }
+
+    // Reserve a path from `find_path_timed`, then keep its goal for `hold` more ticks
+    pub fn reserve_path(&mut self, agent: usize, path: &[Cell], start_tick: u32, hold: u32) {
+        let mut tick = start_tick;
+        for cell in path.iter().rev() {
+            self.reserve(cell.i, cell.j, tick, agent);
+            tick += 1;
+        }
+        if let Some(goal) = path.first() {
+            for extra in 0..hold {
+                self.reserve(goal.i, goal.j, tick + extra, agent);
+            }
+        }
+    }
+
+    // Drop everything `agent` holds, before it plans again
+    pub fn release(&mut self, agent: usize) {
+        self.cells.retain(|_, holder| *holder != agent);
+    }
+
+    // Forget reservations that are in the past
+    pub fn release_before(&mut self, tick: u32) {
+        self.cells.retain(|&(_, _, reserved), _| reserved >= tick);
+    }
+}
+
+// Cell across the corner between sides `a` and `b`. Both ways around the corner have to be open
+// and free of doorways, so a diagonal step never cuts a wall or a door frame.
+fn diagonal_neighbor<'g>(grid: &'g Grid, cell: &Cell, a: Direction, b: Direction) -> Option<&'g Cell> {
+    let (ai, aj) = grid.step(cell.i, cell.j, a)?;
+    let (bi, bj) = grid.step(cell.i, cell.j, b)?;
+    let (ti, tj) = grid.step(ai, aj, b)?;
+    let edges = [(cell.i, cell.j, a), (cell.i, cell.j, b), (ai, aj, b), (bi, bj, a)];
+    let clear = edges.iter().all(|&(i, j, dir)| {
+        grid.is_open(i, j, dir) && grid.get(i, j).map_or(false, |cell| !cell.doors[dir.index()])
+    });
+    if clear {
+        grid.get(ti, tj)
+    } else {
+        None
+    }
+}
+
+// A* from `start_cell` to `end_cell`. The path is returned goal first and ends with the start cell,
+// so walking it is a matter of popping cells off the back.
+pub fn find_path(grid: &Grid, start_cell: &Cell, end_cell: &Cell) -> Option<Vec<Cell>> {
+    find_path_with(grid, start_cell, end_cell, &UniformCost)
+}
+
+// Same as `find_path`, with the step costs of `cost`
+pub fn find_path_with(grid: &Grid, start_cell: &Cell, end_cell: &Cell, cost: &dyn CostModel) -> Option<Vec<Cell>> {
+    Pathfinder::new(cost).find_path(grid, start_cell, end_cell)
+}
+
+// Distance map with orthogonal steps of `DIST_BETWEEN`
+pub fn distance_map(grid: &Grid, sources: &[(usize, usize)]) -> DistanceMap {
+    Pathfinder::new(&UniformCost).distance_map(grid, sources)
 }
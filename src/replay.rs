@@ -0,0 +1,298 @@
+use std::{fs, io, path::{Path, PathBuf}};
+use bevy::{app::AppExit, prelude::*};
+use serde::{Serialize, Deserialize};
+use ron::ser::PrettyConfig;
+use crate::{GameState, game::{Game, GameSystem, Arrival, LevelConfig}, save::{RunSnapshot, RunState, SAVE_VERSION}, sim::{SimStage, SimSystem, SimClock, PlayerInput}, leaderboard, storage};
+
+// Bumped when the input or the simulation changes in a way older replays can't follow
+pub const REPLAY_VERSION: u32 = 4;
+const REPLAY_DIR: &str = "replays";
+
+// A run as its starting state and the player's input at each step, enough to play it again to the same end
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Replay {
+    pub version: u32,
+    // For whoever reads the ticket, the run itself starts from `start`
+    pub seed: u64,
+    pub date: u64,
+    // Size the floors built after the first one grow from, the run's difficulty and mode are in `start`
+    pub size: (usize, usize),
+    pub start: RunSnapshot,
+    // Steps where the input changed, counted from 1. Held keys carry over until the next one
+    pub inputs: Vec<(u64, PlayerInput)>,
+    // Steps recorded and where they led
+    pub steps: u64,
+    pub end: Option<RunSnapshot>,
+}
+
+impl Replay {
+    pub fn new(start: RunSnapshot, size: (usize, usize)) -> Self {
+        Self {
+            version: REPLAY_VERSION,
+            seed: start.game.seed,
+            date: leaderboard::now(),
+            size,
+            start,
+            inputs: Vec::new(),
+            steps: 0,
+            end: None,
+        }
+    }
+
+    // File name it is saved under in the replay folder
+    pub fn file_name(&self) -> String {
+        format!("replay-{}-seed-{}.ron", self.date, self.seed)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        let replay: Replay = ron::de::from_str(&text)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        if replay.version != REPLAY_VERSION || replay.start.version != SAVE_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("recorded by version {}, expected {}", replay.version, REPLAY_VERSION),
+            ));
+        }
+        Ok(replay)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let text = ron::ser::to_string_pretty(self, PrettyConfig::new())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        storage::write_atomic(path, &text)
+    }
+
+    // Note the input of `step`, only kept when it isn't just the keys held on the step before
+    pub fn record(&mut self, step: u64, input: &PlayerInput) {
+        let held = self.inputs.last().map(|(_, last)| last.held()).unwrap_or_default();
+        if *input != held {
+            self.inputs.push((step, input.clone()));
+        }
+        self.steps = step;
+    }
+}
+
+// Folder replays are written to, next to the leaderboard
+pub fn replay_dir() -> PathBuf {
+    storage::data_dir().join(REPLAY_DIR)
+}
+
+// Input of the run being played, from its first step. Starts over with each run, floors reached by the elevator carry on
+#[derive(Default)]
+pub struct Recorder {
+    replay: Option<Replay>,
+    // Clock tick before the first step, `None` until the run took it
+    start: Option<u64>,
+    // Replay of the run that just ended, with where it got to, until it is written
+    finished: Option<Replay>,
+}
+
+impl Recorder {
+    pub fn finished(&self) -> Option<&Replay> {
+        self.finished.as_ref()
+    }
+}
+
+// Replay being played back instead of the player's input
+#[derive(Default)]
+pub struct Playback {
+    replay: Option<Replay>,
+    start: Option<u64>,
+    // Index in the replay's inputs of the next change
+    next: usize,
+    held: PlayerInput,
+    finished: bool,
+}
+
+impl Playback {
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            replay: Some(replay),
+            ..Default::default()
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.replay.is_some()
+    }
+
+    // Input of `step`, steps are asked for in order from 1
+    pub fn input(&mut self, step: u64) -> PlayerInput {
+        let change = self.replay.as_ref().and_then(|replay| replay.inputs.get(self.next)).filter(|(at, _)| *at == step);
+        match change {
+            Some((_, input)) => {
+                let input = input.clone();
+                self.next += 1;
+                self.held = input.held();
+                input
+            },
+            None => self.held.clone(),
+        }
+    }
+
+    // Whether `reached` is where the recording ended up after its last step
+    pub fn matches(&self, step: u64, reached: &RunSnapshot) -> bool {
+        self.replay.as_ref().map_or(false, |replay| replay.steps == step && replay.end.as_ref() == Some(reached))
+    }
+}
+
+// Records the run or plays a replay back, with no window or files so it runs headless too
+pub struct ReplayPlugin;
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<Recorder>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::Game)
+                    .with_system(start_recording.after(GameSystem::SetupLevel))
+            )
+            .add_system_to_stage(SimStage, replay_input.after(SimSystem::Input).before(SimSystem::Player))
+            .add_system_set(
+                SystemSet::on_enter(GameState::GameOver)
+                    .with_system(end_replay)
+            );
+    }
+}
+
+// Writes replays to the replay folder: a run's once it ended, the one in progress with `F6` and on quitting
+pub struct ReplayFilesPlugin;
+impl Plugin for ReplayFilesPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_system_set(
+                SystemSet::on_update(GameState::Game)
+                    .with_system(save_replay_keyboard)
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::GameOver)
+                    .with_system(write_finished_replay)
+            )
+            .add_system_to_stage(CoreStage::PostUpdate, save_replay_on_exit);
+    }
+}
+
+fn write_replay(replay: &Replay) {
+    let path = replay_dir().join(replay.file_name());
+    match replay.save(&path) {
+        Ok(()) => info!("replay saved to {}", path.display()),
+        Err(err) => error!("failed to save {}: {}", path.display(), err),
+    }
+}
+
+// a new or continued run is recorded from its first step
+fn start_recording(game: Res<Game>, mut recorder: ResMut<Recorder>) {
+    if game.arrival != Arrival::Elevator {
+        *recorder = Recorder::default();
+    }
+}
+
+// Record this step's input, or replace it with the replay's
+fn replay_input(
+    mut clock: ResMut<SimClock>,
+    mut input: ResMut<PlayerInput>,
+    mut recorder: ResMut<Recorder>,
+    mut playback: ResMut<Playback>,
+    config: Res<LevelConfig>,
+    run: RunState,
+) {
+    if playback.is_active() {
+        if playback.finished {
+            return;
+        }
+        let start = *playback.start.get_or_insert(clock.tick - 1);
+        let step = clock.tick - start;
+        let steps = playback.replay.as_ref().map_or(0, |replay| replay.steps);
+        if step > steps {
+            // the recording stopped before the run ended, compare where it got to and leave it there
+            let reached = run.snapshot();
+            report_playback(&mut playback, step - 1, &reached);
+            *input = PlayerInput::default();
+            clock.halted = true;
+            return;
+        }
+        *input = playback.input(step);
+        return;
+    }
+    let start = match recorder.start {
+        Some(start) => start,
+        None => {
+            let start = run.snapshot();
+            recorder.replay = Some(Replay::new(start, (config.width, config.length)));
+            recorder.start = Some(clock.tick - 1);
+            clock.tick - 1
+        },
+    };
+    if let Some(replay) = &mut recorder.replay {
+        replay.record(clock.tick - start, &input);
+    }
+}
+
+fn report_playback(playback: &mut Playback, step: u64, reached: &RunSnapshot) {
+    playback.finished = true;
+    if playback.matches(step, reached) {
+        info!("replay played back to the same end after {} steps", step);
+    } else {
+        error!("replay diverged, ended after {} steps in a different state", step);
+    }
+}
+
+// the run is over, finish its replay or check the one played back got to the same end
+fn end_replay(
+    clock: Res<SimClock>,
+    mut recorder: ResMut<Recorder>,
+    mut playback: ResMut<Playback>,
+    run: RunState,
+) {
+    let reached = run.snapshot();
+    if playback.is_active() {
+        if !playback.finished {
+            let step = playback.start.map_or(0, |start| clock.tick - start);
+            report_playback(&mut playback, step, &reached);
+        }
+        return;
+    }
+    if let Some(mut replay) = recorder.replay.take() {
+        replay.end = Some(reached);
+        recorder.finished = Some(replay);
+    }
+    recorder.start = None;
+}
+
+fn write_finished_replay(mut recorder: ResMut<Recorder>) {
+    if let Some(replay) = recorder.finished.take() {
+        write_replay(&replay);
+    }
+}
+
+// `F6` keeps the replay of the run so far, to go with a bug report
+fn save_replay_keyboard(
+    keyboard_input: Res<Input<KeyCode>>,
+    recorder: Res<Recorder>,
+    run: RunState,
+) {
+    if !keyboard_input.just_pressed(KeyCode::F6) {
+        return;
+    }
+    if let Some(replay) = &recorder.replay {
+        let mut replay = replay.clone();
+        replay.end = Some(run.snapshot());
+        write_replay(&replay);
+    }
+}
+
+fn save_replay_on_exit(
+    mut exits: EventReader<AppExit>,
+    state: Res<State<GameState>>,
+    recorder: Res<Recorder>,
+    run: RunState,
+) {
+    if exits.iter().count() == 0 || *state.current() != GameState::Game {
+        return;
+    }
+    if let Some(replay) = &recorder.replay {
+        let mut replay = replay.clone();
+        replay.end = Some(run.snapshot());
+        write_replay(&replay);
+    }
+}
@@ -0,0 +1,149 @@
+use bevy::{prelude::*, render::camera::{Camera, PerspectiveProjection}};
+use crate::{GameState, game::{Game, LevelTag}, grid::{Grid, Direction}, pathfinding::{CostModel, Pathfinder, TerrainCost}, turn, fog::FogOfWar, sim::PendingInput};
+
+// The cell under the mouse cursor and the route the player would walk to get there
+#[derive(Default)]
+pub struct RoutePreview {
+    pub hovered: Option<(usize, usize)>,
+    // Where the route starts, it is planned again once the player moves
+    pub from: (usize, usize),
+    // Door revision of the map it was planned on
+    revision: Option<u32>,
+    // Goal first, the player's own cell left out
+    pub path: Vec<(usize, usize)>,
+    pub cost: i32,
+    pub action_points: u32,
+    markers: Vec<Entity>,
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+
+#[derive(Component)]
+struct RouteMarker;
+
+pub struct RoutePlugin;
+impl Plugin for RoutePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<RoutePreview>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::Game)
+                    .with_system(setup_route)
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Game)
+                    .with_system(hover_route)
+                    .with_system(click_route.after(hover_route))
+            );
+    }
+}
+
+fn setup_route(
+    mut preview: ResMut<RoutePreview>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    *preview = RoutePreview {
+        mesh: meshes.add(Mesh::from(shape::Plane { size: 0.3 })),
+        material: materials.add(StandardMaterial {
+            base_color: Color::rgb(0.2, 0.9, 1.0),
+            unlit: true,
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+}
+
+// Point on the floor under the cursor, turned into the cell it falls in
+pub fn cursor_cell(window: &Window, camera: &Camera, transform: &GlobalTransform, map: &Grid) -> Option<(usize, usize)> {
+    let cursor = window.cursor_position()?;
+    let ndc = cursor / Vec2::new(window.width(), window.height()) * 2.0 - Vec2::ONE;
+    let ndc_to_world = transform.compute_matrix() * camera.projection_matrix.inverse();
+    // the projection is reversed, the near plane sits at depth 1
+    let near = ndc_to_world.project_point3(ndc.extend(1.0));
+    let far = ndc_to_world.project_point3(ndc.extend(0.5));
+    let ray = far - near;
+    if ray.y >= 0.0 {
+        return None;
+    }
+    let floor = near + ray * (-near.y / ray.y);
+    let (i, j) = (floor.x.round() as i32, floor.z.round() as i32);
+    if map.in_bounds(i, j) {
+        Some((i as usize, j as usize))
+    } else {
+        None
+    }
+}
+
+fn hover_route(
+    mut commands: Commands,
+    mut preview: ResMut<RoutePreview>,
+    game: Res<Game>,
+    fog: Res<FogOfWar>,
+    windows: Res<Windows>,
+    cameras: Query<(&Camera, &GlobalTransform), With<PerspectiveProjection>>,
+) {
+    let hovered = match (windows.get_primary(), cameras.iter().next()) {
+        (Some(window), Some((camera, transform))) => cursor_cell(window, camera, transform, &game.map),
+        _ => None,
+    };
+    // no routes into the unknown
+    let hovered = hovered.filter(|&(i, j)| fog.is_explored(i, j));
+    let from = (game.player.i, game.player.j);
+    let revision = game.map.door_revision();
+    if hovered == preview.hovered && from == preview.from && Some(revision) == preview.revision {
+        return;
+    }
+    preview.hovered = hovered;
+    preview.from = from;
+    preview.revision = Some(revision);
+    preview.path.clear();
+    preview.cost = 0;
+    preview.action_points = 0;
+    for entity in preview.markers.drain(..) {
+        commands.entity(entity).despawn();
+    }
+
+    // a keycard opens locked doors like any other
+    let terrain = if game.player.keycard {
+        TerrainCost { unlock: Some(0), ..TerrainCost::player() }
+    } else {
+        TerrainCost::player()
+    };
+    let route = match (hovered.and_then(|(i, j)| game.map.get(i, j)), game.map.get(from.0, from.1)) {
+        (Some(goal), Some(start)) if goal != start => Pathfinder::new(&terrain).find_path_with_cost(&game.map, start, goal),
+        _ => None,
+    };
+    let (path, cost) = match route {
+        Some(route) => route,
+        None => return,
+    };
+    // price every step in action points, the same way moving by hand does
+    for step in path.windows(2) {
+        let (to, from) = (&step[0], &step[1]);
+        if let Some(cost) = Direction::between((from.i, from.j), (to.i, to.j)).and_then(|dir| terrain.step_cost(from, to, dir)) {
+            preview.action_points += turn::action_points(cost);
+        }
+    }
+    preview.cost = cost;
+    preview.path = path[..path.len() - 1].iter().map(|cell| (cell.i, cell.j)).collect();
+    for cell in &path[..path.len() - 1] {
+        let marker = commands.spawn_bundle(PbrBundle {
+            mesh: preview.mesh.clone(),
+            material: preview.material.clone(),
+            transform: Transform::from_xyz(cell.i as f32, cell.height + 0.04, cell.j as f32),
+            ..Default::default()
+        })
+        .insert(RouteMarker)
+        .insert(LevelTag)
+        .id();
+        preview.markers.push(marker);
+    }
+}
+
+// walk the previewed route, one step at a time through `move_player`
+fn click_route(mut pending: ResMut<PendingInput>, preview: Res<RoutePreview>, mouse_input: Res<Input<MouseButton>>) {
+    if mouse_input.just_pressed(MouseButton::Left) && !preview.path.is_empty() {
+        pending.0.route = Some(preview.path.clone());
+    }
+}
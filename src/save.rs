@@ -0,0 +1,184 @@
+use std::{fs, io, marker::PhantomData, path::{Path, PathBuf}};
+use bevy::{app::AppExit, prelude::*, ecs::system::SystemParam};
+use serde::{Serialize, Deserialize};
+use ron::ser::PrettyConfig;
+use crate::{GameState, game::{Game, GameRng, GameSnapshot}, guard::{GuardRoster, RosterSnapshot}, turn::{Turn, TurnSnapshot}, fog::FogOfWar, replay::Playback, storage};
+
+const SAVE_FILE: &str = "save.ron";
+// Bumped when a snapshot changes shape, older saves are refused rather than half read
+pub const SAVE_VERSION: u32 = 2;
+
+// Everything needed to pick a run up where it was left
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunSnapshot {
+    pub version: u32,
+    pub game: GameSnapshot,
+    pub guards: RosterSnapshot,
+    pub turn: TurnSnapshot,
+    pub fog: FogOfWar,
+}
+
+// Parts of a saved run waiting for the level to be built. Each setup system takes its own and
+// starts afresh when there is none
+#[derive(Default)]
+pub struct Restore {
+    pub game: Option<GameSnapshot>,
+    pub guards: Option<RosterSnapshot>,
+    pub turn: Option<TurnSnapshot>,
+    pub fog: Option<FogOfWar>,
+}
+
+impl From<RunSnapshot> for Restore {
+    fn from(run: RunSnapshot) -> Self {
+        Self {
+            game: Some(run.game),
+            guards: Some(run.guards),
+            turn: Some(run.turn),
+            fog: Some(run.fog),
+        }
+    }
+}
+
+impl RunSnapshot {
+    pub fn path() -> PathBuf {
+        storage::data_dir().join(SAVE_FILE)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        let run: RunSnapshot = ron::de::from_str(&text)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        if run.version != SAVE_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("saved by version {}, expected {}", run.version, SAVE_VERSION),
+            ));
+        }
+        Ok(run)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let text = ron::ser::to_string_pretty(self, PrettyConfig::new())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        storage::write_atomic(path, &text)
+    }
+}
+
+// Resources a run is saved from
+#[derive(SystemParam)]
+pub struct RunState<'w, 's> {
+    game: Res<'w, Game>,
+    rng: Res<'w, GameRng>,
+    guards: Res<'w, GuardRoster>,
+    turn: Res<'w, Turn>,
+    fog: Res<'w, FogOfWar>,
+    #[system_param(ignore)]
+    marker: PhantomData<&'s ()>,
+}
+
+impl<'w, 's> RunState<'w, 's> {
+    pub fn snapshot(&self) -> RunSnapshot {
+        RunSnapshot {
+            version: SAVE_VERSION,
+            game: self.game.snapshot(&self.rng),
+            guards: self.guards.snapshot(),
+            turn: self.turn.snapshot(),
+            fog: self.fog.clone(),
+        }
+    }
+}
+
+// Whether there is a run to continue
+pub fn save_exists() -> bool {
+    RunSnapshot::path().is_file()
+}
+
+pub struct SavePlugin;
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<AutosaveDue>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::Game)
+                    .with_system(schedule_autosave)
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Game)
+                    .with_system(quick_save)
+                    .with_system(autosave)
+            )
+            // the pause menu can quit to the main menu, the run is kept for "Continue"
+            .add_system_set(
+                SystemSet::on_pause(GameState::Game)
+                    .with_system(save_on_pause)
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::GameOver)
+                    .with_system(delete_save)
+            )
+            // after the frame's systems, the app closes right after it
+            .add_system_to_stage(CoreStage::PostUpdate, save_on_exit);
+    }
+}
+
+// a replay being watched leaves the player's own save alone
+fn save_run(run: &RunState, playback: &Playback) {
+    if playback.is_active() {
+        return;
+    }
+    let run = run.snapshot();
+    let path = RunSnapshot::path();
+    match run.save(&path) {
+        Ok(()) => info!("run saved to {}", path.display()),
+        Err(err) => error!("failed to save {}: {}", path.display(), err),
+    }
+}
+
+fn quick_save(keyboard_input: Res<Input<KeyCode>>, run: RunState, playback: Res<Playback>) {
+    if keyboard_input.just_pressed(KeyCode::F5) {
+        save_run(&run, &playback);
+    }
+}
+
+// Set when a floor starts, the run is saved once it is up so a crash costs at most the floor in progress
+#[derive(Default)]
+struct AutosaveDue(bool);
+
+fn schedule_autosave(mut due: ResMut<AutosaveDue>) {
+    due.0 = true;
+}
+
+fn autosave(mut due: ResMut<AutosaveDue>, run: RunState, playback: Res<Playback>) {
+    if due.0 {
+        due.0 = false;
+        save_run(&run, &playback);
+    }
+}
+
+fn save_on_pause(run: RunState, playback: Res<Playback>) {
+    save_run(&run, &playback);
+}
+
+fn save_on_exit(
+    mut exits: EventReader<AppExit>,
+    state: Res<State<GameState>>,
+    run: RunState,
+    playback: Res<Playback>,
+) {
+    if exits.iter().count() > 0 && *state.current() == GameState::Game {
+        save_run(&run, &playback);
+    }
+}
+
+// a lost run can't be continued
+fn delete_save(playback: Res<Playback>) {
+    if playback.is_active() {
+        return;
+    }
+    let path = RunSnapshot::path();
+    match fs::remove_file(&path) {
+        Ok(()) => {},
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {},
+        Err(err) => error!("failed to remove {}: {}", path.display(), err),
+    }
+}
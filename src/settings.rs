@@ -0,0 +1,163 @@
+use std::{fs, io, path::{Path, PathBuf}};
+use bevy::{prelude::*, window::WindowMode};
+use serde::{Serialize, Deserialize};
+use ron::ser::PrettyConfig;
+use crate::{difficulty::Difficulty, turn::{Turn, PlayMode}, storage};
+
+const SETTINGS_FILE: &str = "settings.ron";
+
+// Everything the player can set in the menu, kept in `settings.ron` in their config folder.
+// Missing fields fall back to their defaults so older files keep working
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub display: DisplaySettings,
+    pub sound: SoundSettings,
+    pub gameplay: GameplaySettings,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum WindowModeSetting {
+    Windowed,
+    Fullscreen,
+    Borderless,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DisplaySettings {
+    pub window_mode: WindowModeSetting,
+    pub resolution: (u32, u32),
+    pub vsync: bool,
+    // Shadows cast by the level's directional light
+    pub shadows: bool,
+}
+
+// Volumes in percent, kept for when the game plays sound
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SoundSettings {
+    pub master: u32,
+    pub music: u32,
+    pub effects: u32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameplaySettings {
+    pub difficulty: Difficulty,
+    pub mode: PlayMode,
+}
+
+impl Default for DisplaySettings {
+    fn default() -> Self {
+        Self {
+            window_mode: WindowModeSetting::Windowed,
+            resolution: (1280, 720),
+            vsync: true,
+            shadows: false,
+        }
+    }
+}
+
+impl Default for SoundSettings {
+    fn default() -> Self {
+        Self {
+            master: 100,
+            music: 70,
+            effects: 100,
+        }
+    }
+}
+
+impl WindowModeSetting {
+    pub fn window_mode(self) -> WindowMode {
+        match self {
+            WindowModeSetting::Windowed => WindowMode::Windowed,
+            WindowModeSetting::Fullscreen => WindowMode::Fullscreen,
+            WindowModeSetting::Borderless => WindowMode::BorderlessFullscreen,
+        }
+    }
+}
+
+impl Settings {
+    pub fn path() -> PathBuf {
+        storage::config_dir().join(SETTINGS_FILE)
+    }
+
+    // Settings saved at `path`, the defaults when there are none or they can't be read
+    pub fn load<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref();
+        match fs::read_to_string(path) {
+            Ok(text) => ron::de::from_str(&text).unwrap_or_else(|err| {
+                warn!("{}: {}, using the default settings", path.display(), err);
+                Settings::default()
+            }),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Settings::default(),
+            Err(err) => {
+                warn!("{}: {}, using the default settings", path.display(), err);
+                Settings::default()
+            },
+        }
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let text = ron::ser::to_string_pretty(self, PrettyConfig::new())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        storage::write_atomic(path, &text)
+    }
+
+    // Window the game opens with
+    pub fn window_descriptor(&self) -> WindowDescriptor {
+        WindowDescriptor {
+            title: "ErrIO".to_string(),
+            width: self.display.resolution.0 as f32,
+            height: self.display.resolution.1 as f32,
+            vsync: self.display.vsync,
+            mode: self.display.window_mode.window_mode(),
+            ..Default::default()
+        }
+    }
+}
+
+// Sent by the menu once the player changed a setting
+pub struct SettingsChanged;
+
+pub struct SettingsPlugin;
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<Settings>()
+            .add_event::<SettingsChanged>()
+            .add_system(apply_settings);
+    }
+}
+
+// Put changed settings to use right away and keep them for the next start
+fn apply_settings(
+    mut changes: EventReader<SettingsChanged>,
+    settings: Res<Settings>,
+    mut windows: ResMut<Windows>,
+    mut lights: Query<&mut DirectionalLight>,
+    mut difficulty: ResMut<Difficulty>,
+    mut turn: ResMut<Turn>,
+) {
+    if changes.iter().count() == 0 {
+        return;
+    }
+    if let Some(window) = windows.get_primary_mut() {
+        let display = &settings.display;
+        window.set_mode(display.window_mode.window_mode());
+        window.set_resolution(display.resolution.0 as f32, display.resolution.1 as f32);
+        window.set_vsync(display.vsync);
+    }
+    for mut light in lights.iter_mut() {
+        light.shadows_enabled = settings.display.shadows;
+    }
+    // a run keeps the difficulty and mode it was started on, the next one starts with the new ones
+    *difficulty = settings.gameplay.difficulty;
+    turn.next_mode = settings.gameplay.mode;
+    if let Err(err) = settings.save(Settings::path()) {
+        error!("failed to save {}: {}", Settings::path().display(), err);
+    }
+}
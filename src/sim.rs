@@ -0,0 +1,175 @@
+use std::time::Duration;
+use bevy::{prelude::*, ecs::schedule::ShouldRun};
+use serde::{Serialize, Deserialize};
+use crate::{GameState, grid::Direction};
+
+// Seconds of game time in one simulation step, whatever the frame rate
+pub const SIM_STEP: f64 = 1.0 / 60.0;
+
+// Stage the simulation runs in, after the frame's update. It runs as many fixed steps as the frame
+// took, one system after the other so a replay steps the same way
+#[derive(StageLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SimStage;
+
+// Phases of a step. Systems that touch the same state are ordered, the stage would otherwise pick
+// an order of its own each run
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SimSystem {
+    // The player's input for the step is known
+    Input,
+    // The player's actions, in this order
+    Move,
+    Door,
+    Takedown,
+    // The player acted on it, the rest of the level answers after
+    Player,
+    // The elevator was taken or the intel came back
+    Level,
+    // Guards heard the noises, came in and took their step
+    Guards,
+}
+
+// How steps are paced: as the frames' time adds up in the game, or one per update for tests and
+// tools that drive the app headless
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimPace {
+    RealTime,
+    PerUpdate,
+}
+
+impl Default for SimPace {
+    fn default() -> Self {
+        SimPace::RealTime
+    }
+}
+
+// Steps simulated so far
+#[derive(Default)]
+pub struct SimClock {
+    pub tick: u64,
+    // Set by whatever leaves the level, no further step runs until it is entered again
+    pub halted: bool,
+}
+
+// What the player asks for in one step, read from the keyboard and mouse or played back from a replay
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlayerInput {
+    // Arrow keys held, as directions on the board
+    pub steer: Vec<Direction>,
+    pub run: bool,
+    // Pressed since the last step
+    pub end_turn: bool,
+    pub door: bool,
+    pub hack: bool,
+    pub takedown: bool,
+    // Route clicked on the board, goal first
+    pub route: Option<Vec<(usize, usize)>>,
+}
+
+impl PlayerInput {
+    // What carries over to the next step when nothing else happens: the keys still held
+    pub fn held(&self) -> PlayerInput {
+        PlayerInput {
+            steer: self.steer.clone(),
+            run: self.run,
+            ..Default::default()
+        }
+    }
+}
+
+// Input gathered over the frames since the last step, from the keyboard and mouse or handed in by a test
+#[derive(Default)]
+pub struct PendingInput(pub PlayerInput);
+
+pub fn step_duration() -> Duration {
+    Duration::from_secs_f64(SIM_STEP)
+}
+
+pub struct SimPlugin;
+impl Plugin for SimPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<SimClock>()
+            .init_resource::<SimPace>()
+            .init_resource::<PlayerInput>()
+            .init_resource::<PendingInput>()
+            .add_stage_after(
+                CoreStage::Update,
+                SimStage,
+                SystemStage::single_threaded().with_run_criteria(sim_due),
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::Game)
+                    .with_system(resume)
+            )
+            .add_system_to_stage(SimStage, begin_step.label(SimSystem::Input));
+    }
+}
+
+// Frame time not simulated yet
+#[derive(Default)]
+struct StepTimer {
+    accumulated: f64,
+    // Set while the stage runs this frame's steps one after the other
+    looping: bool,
+}
+
+// Steps only run while a level is being played. Time spent paused, in a menu or between floors is
+// dropped, the level doesn't rush to catch up with it
+fn sim_due(
+    mut timer: Local<StepTimer>,
+    time: Res<Time>,
+    clock: Res<SimClock>,
+    pace: Res<SimPace>,
+    state: Res<State<GameState>>,
+) -> ShouldRun {
+    if clock.halted || *state.current() != GameState::Game {
+        *timer = StepTimer::default();
+        return ShouldRun::No;
+    }
+    if *pace == SimPace::PerUpdate {
+        return ShouldRun::Yes;
+    }
+    if !timer.looping {
+        timer.accumulated += time.delta_seconds_f64();
+    }
+    if timer.accumulated >= SIM_STEP {
+        timer.accumulated -= SIM_STEP;
+        timer.looping = true;
+        ShouldRun::YesAndCheckAgain
+    } else {
+        timer.looping = false;
+        ShouldRun::No
+    }
+}
+
+// a new level starts with nothing pressed
+fn resume(mut clock: ResMut<SimClock>, mut pending: ResMut<PendingInput>) {
+    clock.halted = false;
+    *pending = PendingInput::default();
+}
+
+// Keys held now, and the ones pressed since the last step
+pub(crate) fn read_keys(mut pending: ResMut<PendingInput>, keyboard_input: Res<Input<KeyCode>>) {
+    let input = &mut pending.0;
+    input.steer = [
+        (KeyCode::Up, Direction::East),
+        (KeyCode::Down, Direction::West),
+        (KeyCode::Right, Direction::South),
+        (KeyCode::Left, Direction::North),
+    ].into_iter().filter(|&(key, _)| keyboard_input.pressed(key)).map(|(_, dir)| dir).collect();
+    // running is quicker but loud
+    input.run = keyboard_input.pressed(KeyCode::LShift) || keyboard_input.pressed(KeyCode::RShift);
+    input.end_turn |= keyboard_input.just_pressed(KeyCode::Return);
+    input.door |= keyboard_input.just_pressed(KeyCode::E);
+    input.hack |= keyboard_input.just_pressed(KeyCode::H);
+    input.takedown |= keyboard_input.just_pressed(KeyCode::T);
+}
+
+// Hand the gathered input to this step, presses are used up
+fn begin_step(mut clock: ResMut<SimClock>, mut input: ResMut<PlayerInput>, mut pending: ResMut<PendingInput>) {
+    clock.tick += 1;
+    *input = pending.0.clone();
+    pending.0 = input.held();
+}
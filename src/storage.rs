@@ -0,0 +1,55 @@
+use std::{env, fs, io::{self, Write}, path::{Path, PathBuf}};
+
+const APP_DIR: &str = "errio";
+
+// Folder for the player's settings, following the platform's conventions
+pub fn config_dir() -> PathBuf {
+    if cfg!(windows) {
+        env_dir("APPDATA", None)
+    } else if cfg!(target_os = "macos") {
+        env_dir("HOME", Some("Library/Application Support"))
+    } else {
+        env::var_os("XDG_CONFIG_HOME").map(PathBuf::from).filter(|dir| dir.is_absolute())
+            .map(|dir| dir.join(APP_DIR))
+            .unwrap_or_else(|| env_dir("HOME", Some(".config")))
+    }
+}
+
+// Folder for what the game records, like the leaderboard
+pub fn data_dir() -> PathBuf {
+    if cfg!(windows) {
+        env_dir("APPDATA", None)
+    } else if cfg!(target_os = "macos") {
+        env_dir("HOME", Some("Library/Application Support"))
+    } else {
+        env::var_os("XDG_DATA_HOME").map(PathBuf::from).filter(|dir| dir.is_absolute())
+            .map(|dir| dir.join(APP_DIR))
+            .unwrap_or_else(|| env_dir("HOME", Some(".local/share")))
+    }
+}
+
+// `$var/sub/errio`, the working directory when the variable isn't set
+fn env_dir(var: &str, sub: Option<&str>) -> PathBuf {
+    match env::var_os(var) {
+        Some(base) => {
+            let base = PathBuf::from(base);
+            sub.map_or(base.clone(), |sub| base.join(sub)).join(APP_DIR)
+        },
+        None => PathBuf::from("."),
+    }
+}
+
+// Write through a temporary file renamed over `path`, a crash halfway leaves the old file whole
+pub fn write_atomic<P: AsRef<Path>>(path: P, contents: &str) -> io::Result<()> {
+    let path = path.as_ref();
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    let mut file = fs::File::create(&temporary)?;
+    file.write_all(contents.as_bytes())?;
+    // on disk before the rename, or a crash could leave an empty file in its place
+    file.sync_all()?;
+    fs::rename(&temporary, path)
+}
@@ -0,0 +1,184 @@
+use std::time::Duration;
+use bevy::prelude::*;
+use serde::{Serialize, Deserialize};
+use crate::{GameState, pathfinding::DIST_BETWEEN, game::{Game, GameSystem, Arrival}, save::Restore, sim::{SimStage, SimSystem, PlayerInput}};
+
+// Action points the player gets each turn
+pub const PLAYER_ACTION_POINTS: u32 = 5;
+// Steps every guard takes during the enemy phase
+pub const GUARD_STEPS_PER_TURN: u32 = 3;
+// Seconds between guard steps, a full second in real time and quicker during the enemy phase
+const REALTIME_GUARD_STEP: f32 = 1.0;
+const ENEMY_PHASE_STEP: f32 = 0.3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PlayMode {
+    RealTime,
+    TurnBased,
+}
+
+impl Default for PlayMode {
+    fn default() -> Self {
+        PlayMode::TurnBased
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TurnPhase {
+    Player,
+    Enemy,
+}
+
+// Who is acting and with how many action points. In real time the player can always act
+// and guards step on their own clock.
+pub struct Turn {
+    pub mode: PlayMode,
+    // Mode the next run starts in, the settings change it without upsetting the run in progress
+    pub next_mode: PlayMode,
+    pub phase: TurnPhase,
+    pub number: u32,
+    pub action_points: u32,
+    enemy_steps_left: u32,
+    clock: Timer,
+}
+
+// Plain copy of a `Turn`, for saved runs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TurnSnapshot {
+    pub mode: PlayMode,
+    pub phase: TurnPhase,
+    pub number: u32,
+    pub action_points: u32,
+    pub enemy_steps_left: u32,
+    // Seconds into the current guard step
+    pub clock: f32,
+}
+
+impl Default for Turn {
+    fn default() -> Self {
+        Turn::new(PlayMode::default())
+    }
+}
+
+impl Turn {
+    pub fn new(mode: PlayMode) -> Self {
+        let step = match mode {
+            PlayMode::RealTime => REALTIME_GUARD_STEP,
+            PlayMode::TurnBased => ENEMY_PHASE_STEP,
+        };
+        Self {
+            mode,
+            next_mode: mode,
+            phase: TurnPhase::Player,
+            number: 1,
+            action_points: PLAYER_ACTION_POINTS,
+            enemy_steps_left: 0,
+            clock: Timer::from_seconds(step, true),
+        }
+    }
+
+    pub fn snapshot(&self) -> TurnSnapshot {
+        TurnSnapshot {
+            mode: self.mode,
+            phase: self.phase,
+            number: self.number,
+            action_points: self.action_points,
+            enemy_steps_left: self.enemy_steps_left,
+            clock: self.clock.elapsed_secs(),
+        }
+    }
+
+    pub fn restore(saved: &TurnSnapshot) -> Self {
+        let mut turn = Turn::new(saved.mode);
+        turn.phase = saved.phase;
+        turn.number = saved.number;
+        turn.action_points = saved.action_points;
+        turn.enemy_steps_left = saved.enemy_steps_left;
+        turn.clock.set_elapsed(Duration::from_secs_f32(saved.clock));
+        turn
+    }
+
+    pub fn player_can_act(&self) -> bool {
+        self.mode == PlayMode::RealTime || self.phase == TurnPhase::Player
+    }
+
+    // Pay `cost` action points, false when the player can't afford it right now
+    pub fn spend(&mut self, cost: u32) -> bool {
+        match self.mode {
+            PlayMode::RealTime => true,
+            PlayMode::TurnBased if self.phase == TurnPhase::Player && self.action_points >= cost => {
+                self.action_points -= cost;
+                true
+            },
+            PlayMode::TurnBased => false,
+        }
+    }
+
+    // Hand over to the guards
+    pub fn end_player_turn(&mut self) {
+        if self.mode != PlayMode::TurnBased || self.phase != TurnPhase::Player {
+            return;
+        }
+        self.phase = TurnPhase::Enemy;
+        self.enemy_steps_left = GUARD_STEPS_PER_TURN;
+        self.clock.reset();
+    }
+
+    // Advance the clock, true when the guards should take a step this frame.
+    // The enemy phase ends, and a new turn starts, after the guards' last step.
+    pub fn guard_step_due(&mut self, delta: Duration) -> bool {
+        match (self.mode, self.phase) {
+            (PlayMode::RealTime, _) => self.clock.tick(delta).just_finished(),
+            (PlayMode::TurnBased, TurnPhase::Player) => false,
+            (PlayMode::TurnBased, TurnPhase::Enemy) => {
+                if !self.clock.tick(delta).just_finished() {
+                    return false;
+                }
+                self.enemy_steps_left -= 1;
+                if self.enemy_steps_left == 0 {
+                    self.phase = TurnPhase::Player;
+                    self.number += 1;
+                    self.action_points = PLAYER_ACTION_POINTS;
+                }
+                true
+            },
+        }
+    }
+}
+
+// Action points a step of `cost` takes, a plain floor step is one
+pub fn action_points(cost: i32) -> u32 {
+    ((cost + DIST_BETWEEN - 1) / DIST_BETWEEN).max(1) as u32
+}
+
+pub struct TurnPlugin;
+impl Plugin for TurnPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<Turn>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::Game)
+                    .with_system(setup_turn.after(GameSystem::SetupLevel))
+            )
+            .add_system_to_stage(SimStage, end_turn.label(SimSystem::Player).after(SimSystem::Takedown));
+    }
+}
+
+// every level starts on the player's first turn unless a saved run picks up where it was. A run is
+// played in the mode it was started in, floor after floor
+fn setup_turn(mut turn: ResMut<Turn>, mut restore: ResMut<Restore>, game: Res<Game>) {
+    let next_mode = turn.next_mode;
+    *turn = match (restore.turn.take(), game.arrival) {
+        (Some(saved), _) => Turn::restore(&saved),
+        (None, Arrival::NewRun) => Turn::new(next_mode),
+        (None, _) => Turn::new(turn.mode),
+    };
+    turn.next_mode = next_mode;
+}
+
+// `Enter` hands over to the guards
+fn end_turn(mut turn: ResMut<Turn>, input: Res<PlayerInput>) {
+    if input.end_turn {
+        turn.end_player_turn();
+    }
+}
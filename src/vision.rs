@@ -0,0 +1,139 @@
+use std::collections::HashSet;
+use serde::{Serialize, Deserialize};
+use crate::grid::{Grid, Direction};
+
+// Shape of a field of view: how far it reaches, in cells, and how wide the cone is, in degrees
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct VisionParams {
+    pub range: f32,
+    pub angle: f32,
+}
+
+impl VisionParams {
+    pub fn cone(range: f32, angle: f32) -> Self {
+        Self { range, angle }
+    }
+
+    // Sees every way, like the player looking around
+    pub fn all_round(range: f32) -> Self {
+        Self { range, angle: 360.0 }
+    }
+}
+
+// Cells seen from `origin` looking `facing`, in grid order. The origin always sees itself
+pub fn visible_cells(grid: &Grid, origin: (usize, usize), facing: Direction, params: &VisionParams) -> Vec<(usize, usize)> {
+    let reach = params.range.max(0.0).floor() as usize;
+    let (i0, j0) = (origin.0.saturating_sub(reach), origin.1.saturating_sub(reach));
+    let (i1, j1) = ((origin.0 + reach).min(grid.width().saturating_sub(1)), (origin.1 + reach).min(grid.length().saturating_sub(1)));
+    let mut cells = Vec::new();
+    for j in j0..=j1 {
+        for i in i0..=i1 {
+            if can_see(grid, origin, facing, params, (i, j)) {
+                cells.push((i, j));
+            }
+        }
+    }
+    cells
+}
+
+// Whether `target` lies in the cone and nothing blocks the line to it
+pub fn can_see(grid: &Grid, origin: (usize, usize), facing: Direction, params: &VisionParams, target: (usize, usize)) -> bool {
+    in_cone(origin, facing, params, target) && line_of_sight(grid, origin, target)
+}
+
+// Whether `target` is within range and angle of the cone, walls ignored
+pub fn in_cone(origin: (usize, usize), facing: Direction, params: &VisionParams, target: (usize, usize)) -> bool {
+    if origin == target {
+        return true;
+    }
+    let (dx, dy) = (target.0 as f32 - origin.0 as f32, target.1 as f32 - origin.1 as f32);
+    let distance = (dx * dx + dy * dy).sqrt();
+    if distance > params.range {
+        return false;
+    }
+    if params.angle >= 360.0 {
+        return true;
+    }
+    let (fx, fy) = facing.offset();
+    let cos = (dx * fx as f32 + dy * fy as f32) / distance;
+    // a little slack so cells right on the edge of the cone count
+    cos >= (params.angle.to_radians() / 2.0).cos() - 1e-4
+}
+
+// Where a watcher stands and the cells it is about to walk, next first
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatcherView {
+    pub at: (usize, usize),
+    pub facing: Direction,
+    pub ahead: Vec<(usize, usize)>,
+}
+
+// Cells watched right now, and cells that will be watched from the moves ahead but aren't yet
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DangerTiles {
+    pub watched: HashSet<(usize, usize)>,
+    pub upcoming: HashSet<(usize, usize)>,
+}
+
+// Danger tiles of a set of watchers, each looking along the way it walks
+pub fn danger_tiles(grid: &Grid, views: &[WatcherView], params: &VisionParams) -> DangerTiles {
+    let mut tiles = DangerTiles::default();
+    for view in views {
+        tiles.watched.extend(visible_cells(grid, view.at, view.facing, params));
+        let (mut at, mut facing) = (view.at, view.facing);
+        for &next in &view.ahead {
+            facing = Direction::between(at, next).unwrap_or(facing);
+            at = next;
+            tiles.upcoming.extend(visible_cells(grid, at, facing, params));
+        }
+    }
+    let watched = &tiles.watched;
+    tiles.upcoming.retain(|cell| !watched.contains(cell));
+    tiles
+}
+
+// Walk the straight line between the centers of two cells and check every edge it crosses.
+// A line through a corner gets by when either way around it is open.
+pub fn line_of_sight(grid: &Grid, from: (usize, usize), to: (usize, usize)) -> bool {
+    let (dx, dy) = (to.0 as i32 - from.0 as i32, to.1 as i32 - from.1 as i32);
+    let (nx, ny) = (dx.abs(), dy.abs());
+    let step_x = if dx > 0 { Direction::East } else { Direction::West };
+    let step_y = if dy > 0 { Direction::South } else { Direction::North };
+    let mut at = from;
+    let (mut ix, mut iy) = (0, 0);
+    while ix < nx || iy < ny {
+        // the ray leaves the cell through the side it reaches first, at (0.5 + ix) / nx or (0.5 + iy) / ny
+        let decision = if ix == nx {
+            1
+        } else if iy == ny {
+            -1
+        } else {
+            (1 + 2 * ix) * ny - (1 + 2 * iy) * nx
+        };
+        let next = if decision < 0 {
+            ix += 1;
+            cross(grid, at, step_x)
+        } else if decision > 0 {
+            iy += 1;
+            cross(grid, at, step_y)
+        } else {
+            ix += 1;
+            iy += 1;
+            cross(grid, at, step_x).and_then(|cell| cross(grid, cell, step_y))
+                .or_else(|| cross(grid, at, step_y).and_then(|cell| cross(grid, cell, step_x)))
+        };
+        at = match next {
+            Some(cell) => cell,
+            None => return false,
+        };
+    }
+    true
+}
+
+fn cross(grid: &Grid, (i, j): (usize, usize), dir: Direction) -> Option<(usize, usize)> {
+    if grid.is_open(i, j, dir) {
+        grid.step(i, j, dir)
+    } else {
+        None
+    }
+}
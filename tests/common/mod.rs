@@ -0,0 +1,86 @@
+// Fixtures shared by the integration tests, each file uses its own share of them
+#![allow(dead_code)]
+
+use std::{fs, path::PathBuf};
+use rand::SeedableRng;
+use errio::{Cell, levelgen::{self, LevelRng}, grid::{Grid, Direction}, fog::FogOfWar, difficulty::Difficulty, pathfinding::ReservationTable, guard::RosterSnapshot, game::{GameSnapshot, PlayerSnapshot, BonusSnapshot}, turn::{Turn, PlayMode}, save::{RunSnapshot, SAVE_VERSION}};
+
+// Room without inner walls
+pub fn open_grid(width: usize, length: usize) -> Grid {
+    let cells = (0..length).flat_map(|j| {
+        (0..width).map(move |i| Cell {
+            open_sides: [j != 0, i != width - 1, j != length - 1, i != 0],
+            i,
+            j,
+            ..Default::default()
+        })
+    }).collect();
+    Grid::new(width, length, cells)
+}
+
+// Wall between a cell and its neighbour
+pub fn close(grid: &mut Grid, i: usize, j: usize, dir: Direction) {
+    let (x, y) = grid.step(i, j, dir).unwrap();
+    grid.get_mut(i, j).unwrap().open_sides[dir.index()] = false;
+    grid.get_mut(x, y).unwrap().open_sides[dir.opposite().index()] = false;
+}
+
+// Folder of a test's own files, removed with them when the test is done
+pub struct ScratchDir(PathBuf);
+
+impl ScratchDir {
+    // `name` keeps tests running side by side out of each other's files
+    pub fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("errio-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+
+    pub fn file(&self, name: &str) -> PathBuf {
+        self.0.join(name)
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+// A run on the first step of its first floor, generated from `seed` on a 12x16 map
+pub fn new_run(seed: u64) -> RunSnapshot {
+    let (map, _) = levelgen::generate_level(12, 16, 7, 3, Some(seed));
+    // as loaded, before any door was touched
+    let map = Grid::new(12, 16, map.into_cells());
+    let spawn = levelgen::spawn_point(12, 16);
+    RunSnapshot {
+        version: SAVE_VERSION,
+        game: GameSnapshot {
+            player: PlayerSnapshot { i: spawn.0, j: spawn.1, facing: Direction::East, keycard: false, route: Vec::new() },
+            score: 0,
+            seed,
+            difficulty: Difficulty::Normal,
+            floor: 1,
+            floor_intel: 0,
+            spawn,
+            exit: levelgen::exit_point(&map, spawn),
+            patrols: Vec::new(),
+            bonus_spots: Vec::new(),
+            bonus: BonusSnapshot { active: false, i: 0, j: 0, spawned: 0, collected_on: 0, discovered: false, respawn: 0.0 },
+            rng: LevelRng::seed_from_u64(seed),
+            map: map.clone(),
+        },
+        guards: RosterSnapshot {
+            guards: Vec::new(),
+            tick: 0,
+            reservations: ReservationTable::default(),
+            next_id: 0,
+            in_view: Vec::new(),
+            knocked_out: 0,
+            profile: Difficulty::Normal.profile(),
+        },
+        turn: Turn::new(PlayMode::TurnBased).snapshot(),
+        fog: FogOfWar::new(&map),
+    }
+}
@@ -0,0 +1,32 @@
+use errio::{difficulty::Difficulty, guard, levelgen, pathfinding::{Pathfinder, TerrainCost, DIST_BETWEEN}, vision};
+
+#[test]
+fn guards_grow_with_floors_and_intel_up_to_the_cap() {
+    for difficulty in Difficulty::ALL {
+        let profile = difficulty.profile();
+        assert_eq!(profile.guards_wanted(1, 0), profile.initial_guards);
+        assert!(profile.guards_wanted(2, 0) >= profile.guards_wanted(1, 0));
+        assert!(profile.guards_wanted(1, 6) > profile.guards_wanted(1, 0));
+        assert_eq!(profile.guards_wanted(50, 50), profile.max_guards);
+        assert!(!profile.tiers_on(1).is_empty());
+    }
+    let (easy, hard) = (Difficulty::Easy.profile(), Difficulty::Hard.profile());
+    assert!(easy.guards_wanted(3, 2) < hard.guards_wanted(3, 2));
+    assert!(easy.tiers_on(1).len() < hard.tiers_on(1).len());
+}
+
+#[test]
+fn guards_spawn_out_of_sight_and_within_reach() {
+    for seed in 0..20 {
+        let (grid, _) = levelgen::generate_level(12, 16, 7, 3, Some(seed));
+        let player = levelgen::spawn_point(12, 16);
+        let spots = guard::spawn_spots(&grid, player, 6);
+        assert!(!spots.is_empty(), "seed {}", seed);
+        let cost = TerrainCost::guard();
+        let distances = Pathfinder::new(&cost).distance_map(&grid, &[player]);
+        for &(i, j) in &spots {
+            assert!(distances.cost(i, j).map_or(false, |cost| cost >= 6 * DIST_BETWEEN), "seed {} ({}, {})", seed, i, j);
+            assert!(!vision::line_of_sight(&grid, player, (i, j)), "seed {} ({}, {})", seed, i, j);
+        }
+    }
+}
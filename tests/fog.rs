@@ -0,0 +1,51 @@
+use errio::{grid::Direction, fog::{FogOfWar, FogState, PLAYER_VISION}, vision::VisionParams};
+
+mod common;
+use common::{open_grid, close};
+
+#[test]
+fn cells_start_unexplored() {
+    let grid = open_grid(4, 4);
+    let fog = FogOfWar::new(&grid);
+    assert!(fog.states().iter().all(|&state| state == FogState::Unexplored));
+    assert_eq!(fog.state(10, 10), FogState::Unexplored);
+}
+
+#[test]
+fn cells_left_behind_are_remembered() {
+    let grid = open_grid(10, 1);
+    let mut fog = FogOfWar::new(&grid);
+    let params = VisionParams::all_round(2.0);
+    fog.reveal(&grid, (0, 0), &params);
+    assert!(fog.is_visible(2, 0));
+    assert_eq!(fog.state(3, 0), FogState::Unexplored);
+    fog.reveal(&grid, (6, 0), &params);
+    assert_eq!(fog.state(0, 0), FogState::Remembered);
+    assert_eq!(fog.state(2, 0), FogState::Remembered);
+    assert_eq!(fog.state(3, 0), FogState::Unexplored);
+    assert!(fog.is_visible(4, 0) && fog.is_visible(8, 0));
+}
+
+#[test]
+fn walls_keep_the_next_room_unexplored() {
+    let mut grid = open_grid(6, 3);
+    for j in 0..3 {
+        close(&mut grid, 2, j, Direction::East);
+    }
+    let mut fog = FogOfWar::new(&grid);
+    fog.reveal(&grid, (1, 1), &PLAYER_VISION);
+    assert!(fog.is_visible(2, 2));
+    assert!((3..6).all(|i| (0..3).all(|j| !fog.is_explored(i, j))));
+}
+
+#[test]
+fn fog_survives_a_round_trip_through_ron() {
+    let grid = open_grid(5, 5);
+    let mut fog = FogOfWar::new(&grid);
+    fog.reveal(&grid, (2, 2), &VisionParams::all_round(1.0));
+    fog.reveal(&grid, (4, 4), &VisionParams::all_round(1.0));
+    fog.last_seen.insert(3, (0, 4));
+    let text = ron::ser::to_string(&fog).unwrap();
+    let back: FogOfWar = ron::de::from_str(&text).unwrap();
+    assert_eq!(back, fog);
+}
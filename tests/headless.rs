@@ -0,0 +1,573 @@
+use bevy::prelude::*;
+use errio::{GameState, DoorState, levelgen, difficulty::Difficulty, Terrain, grid::{Grid, Direction}, game::{Arrival, Game, GamePlugin, GameRng, LevelConfig, FloorStart}, guard::{self, GuardRoster, GuardSnapshot, GuardState, RosterSnapshot, GUARD_VISION}, turn::{self, Turn, TurnPhase, PlayMode}, fog::FogOfWar, pathfinding::{CostModel, TerrainCost}, save::{Restore, RunSnapshot, SAVE_VERSION}, replay::{Playback, Recorder, ReplayPlugin}, menu::PausePlugin, sim::{PendingInput, PlayerInput, SimClock, SimPace}, vision};
+
+// A run with no window, assets or input devices, one simulation step per update
+struct Harness {
+    app: App,
+}
+
+impl Harness {
+    fn new(seed: u64, mode: PlayMode) -> Self {
+        Self::start(seed, mode, Restore::default())
+    }
+
+    fn start(seed: u64, mode: PlayMode, restore: Restore) -> Self {
+        let mut app = App::new();
+        app
+            .insert_resource(LevelConfig { seed: Some(seed), ..Default::default() })
+            .insert_resource(Turn::new(mode))
+            .insert_resource(SimPace::PerUpdate)
+            .insert_resource(restore)
+            .init_resource::<Input<KeyCode>>()
+            .add_plugins(MinimalPlugins)
+            .add_state(GameState::Game)
+            .add_plugin(GamePlugin)
+            .add_plugin(PausePlugin);
+        Self { app }
+    }
+
+    fn steps(&mut self, count: usize) {
+        for _ in 0..count {
+            self.app.update();
+        }
+    }
+
+    // Held keys carry over from step to step until the next input
+    fn input(&mut self, input: PlayerInput) {
+        self.app.world.get_resource_mut::<PendingInput>().unwrap().0 = input;
+    }
+
+    fn game(&self) -> &Game {
+        self.app.world.get_resource::<Game>().unwrap()
+    }
+
+    fn turn(&self) -> &Turn {
+        self.app.world.get_resource::<Turn>().unwrap()
+    }
+
+    fn guards(&self) -> RosterSnapshot {
+        self.app.world.get_resource::<GuardRoster>().unwrap().snapshot()
+    }
+
+    fn state(&self) -> GameState {
+        self.app.world.get_resource::<State<GameState>>().unwrap().current().clone()
+    }
+
+    fn tick(&self) -> u64 {
+        self.app.world.get_resource::<SimClock>().unwrap().tick
+    }
+
+    // Pressed for the next update, nothing releases it without an input plugin
+    fn press(&mut self, key: KeyCode) {
+        self.app.world.get_resource_mut::<Input<KeyCode>>().unwrap().press(key);
+    }
+
+    // `Escape`, the state changes on the next update
+    fn pause(&mut self) {
+        self.press(KeyCode::Escape);
+    }
+
+    fn resume(&mut self) {
+        self.press(KeyCode::Escape);
+    }
+
+    // What the pause menu's "Restart Level" does
+    fn restart(&mut self) -> bool {
+        let world = &mut self.app.world;
+        world.resource_scope(|world, start: Mut<FloorStart>| {
+            world.resource_scope(|world, mut restore: Mut<Restore>| {
+                start.restart(&mut restore, &mut world.get_resource_mut::<State<GameState>>().unwrap())
+            })
+        })
+    }
+
+    fn snapshot(&self) -> RunSnapshot {
+        let world = &self.app.world;
+        RunSnapshot {
+            version: SAVE_VERSION,
+            game: self.game().snapshot(world.get_resource::<GameRng>().unwrap()),
+            guards: self.guards(),
+            turn: self.turn().snapshot(),
+            fog: world.get_resource::<FogOfWar>().unwrap().clone(),
+        }
+    }
+}
+
+// A way out of the player's cell with no wall or shut door, affordable in one turn
+fn free_step(game: &Game) -> (Direction, (usize, usize)) {
+    let (i, j) = (game.player.i, game.player.j);
+    Direction::ALL.iter().copied().find_map(|dir| {
+        let to = game.map.step(i, j, dir).filter(|_| game.map.is_open(i, j, dir))?;
+        if game.map.door(i, j, dir).map_or(false, |state| state != DoorState::Open) {
+            return None;
+        }
+        let cost = TerrainCost::player().step_cost(game.map.get(i, j)?, game.map.get(to.0, to.1)?, dir)?;
+        if turn::action_points(cost) > turn::PLAYER_ACTION_POINTS {
+            return None;
+        }
+        Some((dir, to))
+    }).expect("the spawn has a way out")
+}
+
+// A guard standing on `at` with the first tier's sight, its patrol is that one cell
+fn guard_at(at: (usize, usize), rotation: Direction, state: GuardState) -> GuardSnapshot {
+    GuardSnapshot {
+        id: 0,
+        i: at.0,
+        j: at.1,
+        rotation,
+        current_path: None,
+        pp: 0,
+        patrol_points: vec![at],
+        state,
+        vision: GUARD_VISION,
+        model: 0,
+    }
+}
+
+// The run with `guard` as the only one on the floor, no other walks in
+fn alone_with(mut saved: RunSnapshot, guard: GuardSnapshot) -> RunSnapshot {
+    saved.guards = RosterSnapshot {
+        guards: vec![guard],
+        next_id: 1,
+        knocked_out: saved.guards.profile.max_guards,
+        ..saved.guards
+    };
+    saved
+}
+
+// Where a guard with the first tier's sight stands and looks to see `player` from a distance `wanted` takes
+fn watching(map: &Grid, player: (usize, usize), wanted: impl Fn(f32) -> bool) -> ((usize, usize), Direction) {
+    map.iter()
+        .flat_map(|cell| Direction::ALL.iter().map(move |&dir| ((cell.i, cell.j), dir)))
+        .filter(|&(at, _)| at != player && wanted(distance(at, player)))
+        .find(|&(at, dir)| vision::can_see(map, at, dir, &GUARD_VISION, player))
+        .expect("a guard could see the player from there")
+}
+
+fn distance(a: (usize, usize), b: (usize, usize)) -> f32 {
+    let (dx, dy) = (a.0 as f32 - b.0 as f32, a.1 as f32 - b.1 as f32);
+    (dx * dx + dy * dy).sqrt()
+}
+
+// What a guard is up to, without the details
+fn doing(state: GuardState) -> &'static str {
+    match state {
+        GuardState::Patrol => "patrol",
+        GuardState::Suspicious { .. } => "suspicious",
+        GuardState::Investigating { .. } => "investigating",
+        GuardState::Hunting { .. } => "hunting",
+    }
+}
+
+// Real-time steps with the only guard far out of the player's sight, what it went through until it
+// was back on patrol
+fn left_alone(seed: u64, state: impl Fn((usize, usize)) -> GuardState) -> Vec<&'static str> {
+    let mut run = Harness::new(seed, PlayMode::RealTime);
+    run.steps(1);
+    let saved = run.snapshot();
+    let player = (saved.game.player.i, saved.game.player.j);
+    let far = saved.game.map.iter().map(|cell| (cell.i, cell.j))
+        .max_by(|&a, &b| distance(a, player).partial_cmp(&distance(b, player)).unwrap())
+        .unwrap();
+    let mut run = Harness::start(seed, PlayMode::RealTime, Restore::from(alone_with(saved, guard_at(far, Direction::North, state(far)))));
+    let mut states = Vec::new();
+    while states.last() != Some(&"patrol") && run.tick() < 3000 {
+        run.steps(1);
+        let guard = run.guards().guards[0].clone();
+        assert!(!vision::can_see(&run.game().map, (guard.i, guard.j), guard.rotation, &guard.vision, player), "seen from {:?}", (guard.i, guard.j));
+        if states.last() != Some(&doing(guard.state)) {
+            states.push(doing(guard.state));
+        }
+    }
+    states
+}
+
+// The run with the player on the exit and enough intel to take the elevator on the next step
+fn on_the_exit(run: &Harness) -> RunSnapshot {
+    let mut saved = run.snapshot();
+    saved.game.player.i = saved.game.exit.0;
+    saved.game.player.j = saved.game.exit.1;
+    saved.game.floor_intel = run.game().intel_required();
+    saved
+}
+
+#[test]
+fn the_player_walks_where_steered() {
+    let mut run = Harness::new(11, PlayMode::TurnBased);
+    run.steps(1);
+    let spawn = (run.game().player.i, run.game().player.j);
+    assert_eq!(spawn, run.game().spawn);
+    let (dir, to) = free_step(run.game());
+
+    run.input(PlayerInput { steer: vec![dir], ..Default::default() });
+    let mut waited = 0;
+    while (run.game().player.i, run.game().player.j) == spawn && waited < 60 {
+        run.steps(1);
+        waited += 1;
+    }
+    run.input(PlayerInput::default());
+    assert_eq!((run.game().player.i, run.game().player.j), to, "still on the spawn after {} steps", waited);
+    assert_eq!(run.game().player.facing, dir);
+    assert!(run.turn().action_points < turn::PLAYER_ACTION_POINTS);
+
+    // nothing held, nothing moves
+    run.steps(60);
+    assert_eq!((run.game().player.i, run.game().player.j), to);
+}
+
+#[test]
+fn ending_the_turn_hands_over_to_the_guards() {
+    let mut run = Harness::new(4, PlayMode::TurnBased);
+    run.steps(1);
+    let guards = run.guards();
+    assert_eq!(guards.guards.len(), guards.profile.guards_wanted(1, 0));
+    assert_eq!(guards.tick, 0);
+
+    run.input(PlayerInput { end_turn: true, ..Default::default() });
+    run.steps(1);
+    assert_eq!(run.turn().phase, TurnPhase::Enemy);
+    // the guards take their steps, then it is the player's turn again
+    run.steps(120);
+    assert_eq!(run.turn().phase, TurnPhase::Player);
+    assert_eq!(run.turn().number, 2);
+    assert_eq!(run.turn().action_points, turn::PLAYER_ACTION_POINTS);
+    assert_eq!(run.guards().tick, turn::GUARD_STEPS_PER_TURN);
+}
+
+#[test]
+fn guards_patrol_on_their_own_clock_in_real_time() {
+    let mut run = Harness::new(8, PlayMode::RealTime);
+    run.steps(1);
+    let before = run.guards();
+    assert!(!before.guards.is_empty());
+    // a guard steps once a second
+    run.steps(150);
+    let after = run.guards();
+    assert_eq!(after.tick, 2);
+    assert!(
+        before.guards.iter().zip(&after.guards).any(|(before, after)| (before.i, before.j) != (after.i, after.j)),
+        "no guard moved",
+    );
+}
+
+#[test]
+fn the_same_seed_and_input_play_out_the_same() {
+    let play = |seed: u64| {
+        let mut run = Harness::new(seed, PlayMode::RealTime);
+        run.steps(1);
+        let (dir, _) = free_step(run.game());
+        run.input(PlayerInput { steer: vec![dir], run: true, ..Default::default() });
+        run.steps(40);
+        run.input(PlayerInput::default());
+        run.steps(200);
+        run.snapshot()
+    };
+    assert_eq!(play(21), play(21));
+    assert_ne!(play(21).game.map, play(22).game.map);
+}
+
+#[test]
+fn a_hunter_next_to_the_player_ends_the_run() {
+    let mut run = Harness::new(6, PlayMode::TurnBased);
+    run.steps(1);
+    let saved = run.snapshot();
+    let player = (saved.game.player.i, saved.game.player.j);
+    let (dir, at) = free_step(run.game());
+    assert!(vision::line_of_sight(&saved.game.map, at, player));
+    let saved = alone_with(saved, guard_at(at, dir.opposite(), GuardState::Hunting { last_seen: player, lost: 0 }));
+
+    let mut caught = Harness::start(6, PlayMode::TurnBased, Restore::from(saved));
+    // caught on the first step, the state changes on the next update
+    caught.steps(2);
+    assert_eq!(caught.state(), GameState::GameOver);
+    assert!(caught.app.world.get_resource::<SimClock>().unwrap().halted);
+    // the run stays where it ended
+    let tick = caught.tick();
+    caught.steps(10);
+    assert_eq!(caught.tick(), tick);
+}
+
+#[test]
+fn nothing_moves_while_paused() {
+    let mut run = Harness::new(8, PlayMode::RealTime);
+    run.steps(1);
+    let (dir, _) = free_step(run.game());
+    run.input(PlayerInput { steer: vec![dir], ..Default::default() });
+    run.pause();
+    run.steps(1);
+    assert_eq!(run.state(), GameState::Paused);
+    let (paused, tick) = (run.snapshot(), run.tick());
+    // guards, intel and the player would all have moved by now
+    run.steps(300);
+    assert_eq!(run.tick(), tick);
+    assert_eq!(run.snapshot(), paused);
+
+    run.resume();
+    run.steps(1);
+    assert_eq!(run.state(), GameState::Game);
+    run.steps(120);
+    assert_eq!(run.tick(), tick + 121);
+    assert_ne!((run.game().player.i, run.game().player.j), run.game().spawn);
+    assert!(run.guards().tick >= 2);
+}
+
+#[test]
+fn escape_pauses_and_resumes_once_per_press() {
+    let mut run = Harness::new(4, PlayMode::RealTime);
+    run.steps(1);
+    run.press(KeyCode::Escape);
+    run.steps(1);
+    assert_eq!(run.state(), GameState::Paused);
+    // the press is used up by the pause, the pause menu doesn't resume on it
+    run.steps(5);
+    assert_eq!(run.state(), GameState::Paused);
+    assert!(!run.app.world.get_resource::<Input<KeyCode>>().unwrap().pressed(KeyCode::Escape));
+
+    run.press(KeyCode::Escape);
+    run.steps(1);
+    assert_eq!(run.state(), GameState::Game);
+    let tick = run.tick();
+    run.steps(5);
+    assert_eq!(run.state(), GameState::Game);
+    assert_eq!(run.tick(), tick + 5);
+}
+
+#[test]
+fn restarting_goes_back_to_the_start_of_the_floor() {
+    let mut run = Harness::new(11, PlayMode::TurnBased);
+    run.steps(1);
+    let started = run.snapshot();
+    let (dir, to) = free_step(run.game());
+    run.input(PlayerInput { steer: vec![dir], ..Default::default() });
+    run.steps(30);
+    run.input(PlayerInput { end_turn: true, ..Default::default() });
+    run.steps(60);
+    assert_eq!((run.game().player.i, run.game().player.j), to);
+    assert_eq!(run.turn().number, 2);
+
+    run.pause();
+    run.steps(1);
+    assert!(run.restart());
+    // torn down and built again, then the first step of the floor once more
+    run.steps(3);
+    assert_eq!(run.state(), GameState::Game);
+    assert_eq!(run.snapshot(), started);
+}
+
+#[test]
+fn a_run_keeps_the_difficulty_it_started_on() {
+    let mut run = Harness::new(3, PlayMode::TurnBased);
+    run.app.insert_resource(Difficulty::Hard);
+    run.steps(1);
+    let mut saved = run.snapshot().game;
+    assert_eq!(saved.difficulty, Difficulty::Hard);
+    saved.difficulty = Difficulty::Easy;
+
+    // the settings say hard, the saved run was started on easy and its floor is built again
+    let mut continued = Harness::start(3, PlayMode::TurnBased, Restore { game: Some(saved), ..Default::default() });
+    continued.app.insert_resource(Difficulty::Hard);
+    continued.steps(1);
+    assert_eq!(continued.game().difficulty, Difficulty::Easy);
+    assert_eq!(continued.guards().profile, Difficulty::Easy.profile());
+}
+
+#[test]
+fn a_run_keeps_its_mode_up_the_elevator() {
+    let mut run = Harness::new(5, PlayMode::TurnBased);
+    run.steps(1);
+    let mut up = Harness::start(5, PlayMode::TurnBased, Restore::from(on_the_exit(&run)));
+    // the settings change while the run goes on
+    up.app.world.get_resource_mut::<Turn>().unwrap().next_mode = PlayMode::RealTime;
+    // taken on the first step, then one update between the floors
+    up.steps(3);
+    assert_eq!(up.state(), GameState::Game);
+    assert_eq!(up.game().floor, 2);
+    assert_eq!(up.turn().mode, PlayMode::TurnBased);
+    assert_eq!(up.turn().next_mode, PlayMode::RealTime);
+}
+
+#[test]
+fn a_replay_plays_back_to_the_same_game_over() {
+    let mut run = Harness::new(6, PlayMode::RealTime);
+    run.steps(1);
+    // a hunter two steps away, the player runs off and waits for it
+    let saved = run.snapshot();
+    let (away, next) = free_step(run.game());
+    let player = (saved.game.player.i, saved.game.player.j);
+    let map = &saved.game.map;
+    let at = Direction::ALL.iter()
+        .filter(|&&dir| map.is_open(next.0, next.1, dir) && map.door(next.0, next.1, dir).is_none())
+        .filter_map(|&dir| map.step(next.0, next.1, dir))
+        .find(|&cell| cell != player)
+        .expect("a cell past the first step");
+    let saved = alone_with(saved, guard_at(at, away.opposite(), GuardState::Hunting { last_seen: player, lost: 0 }));
+
+    let mut recorded = Harness::start(6, PlayMode::RealTime, Restore::from(saved));
+    recorded.app.add_plugin(ReplayPlugin);
+    recorded.steps(1);
+    recorded.input(PlayerInput { steer: vec![away.opposite()], run: true, ..Default::default() });
+    recorded.steps(10);
+    recorded.input(PlayerInput::default());
+    while recorded.state() != GameState::GameOver && recorded.tick() < 1200 {
+        recorded.steps(1);
+    }
+    assert_eq!(recorded.state(), GameState::GameOver);
+    recorded.steps(1);
+    let replay = recorded.app.world.get_resource::<Recorder>().unwrap().finished().cloned().expect("the run's replay");
+    assert_eq!(replay.steps, recorded.tick());
+    assert!(replay.steps > 10, "caught after {} steps", replay.steps);
+    assert!(!replay.inputs.is_empty());
+    assert_eq!(replay.end, Some(recorded.snapshot()));
+
+    // nothing held this time, the replay's input is played instead
+    let mut played = Harness::start(99, PlayMode::TurnBased, Restore::from(replay.start.clone()));
+    played.app.insert_resource(Playback::new(replay.clone())).add_plugin(ReplayPlugin);
+    while played.state() != GameState::GameOver && played.tick() < 1200 {
+        played.steps(1);
+    }
+    assert_eq!(played.state(), GameState::GameOver);
+    assert!(played.app.world.get_resource::<Playback>().unwrap().matches(played.tick(), &played.snapshot()));
+}
+
+#[test]
+fn danger_tiles_match_what_guards_detect() {
+    let mut run = Harness::new(2, PlayMode::TurnBased);
+    run.steps(1);
+    let saved = run.snapshot();
+    let map = saved.game.map.clone();
+    let at = levelgen::spawn_point(map.width(), map.length());
+    let tiers = Difficulty::Hard.profile().tiers;
+    for (tier, facing) in tiers.iter().zip([Direction::East, Direction::South, Direction::West]) {
+        let guard = GuardSnapshot { vision: tier.vision, model: tier.model, ..guard_at(at, facing, GuardState::Patrol) };
+        let reach = tier.vision.range.ceil() as i32 + 1;
+        let (mut tiles, mut spotted) = (None, 0);
+        for cell in map.iter().filter(|cell| (cell.i, cell.j) != at && (cell.i as i32 - at.0 as i32).abs() + (cell.j as i32 - at.1 as i32).abs() <= reach) {
+            let mut placed = alone_with(saved.clone(), guard.clone());
+            placed.game.player.i = cell.i;
+            placed.game.player.j = cell.j;
+            let mut run = Harness::start(2, PlayMode::TurnBased, Restore::from(placed));
+            run.steps(1);
+            // the overlay as drawn for this guard, as if the player could see it
+            let tiles = tiles.get_or_insert_with(|| {
+                let mut fog = FogOfWar::new(&map);
+                fog.reveal(&map, at, &tier.vision);
+                let views = run.app.world.get_resource::<GuardRoster>().unwrap().danger_views(&fog, PlayMode::TurnBased);
+                assert_eq!(views.len(), 1);
+                guard::danger_tiles(&map, &views)
+            });
+            let detected = run.guards().guards[0].state != GuardState::Patrol;
+            assert_eq!(tiles.watched.contains(&(cell.i, cell.j)), detected, "{:?} facing {:?}, player at {:?}", tier.vision, facing, (cell.i, cell.j));
+            spotted += detected as usize;
+        }
+        assert!(spotted > 1, "{:?} facing {:?} saw {} cells", tier.vision, facing, spotted);
+    }
+}
+
+#[test]
+fn a_glimpse_makes_a_guard_suspicious_and_a_clear_view_makes_it_hunt() {
+    let mut run = Harness::new(9, PlayMode::TurnBased);
+    run.steps(1);
+    let saved = run.snapshot();
+    let map = &saved.game.map;
+    let player = (saved.game.player.i, saved.game.player.j);
+    assert_ne!(map.get(player.0, player.1).unwrap().terrain, Terrain::Shadow);
+
+    // at the far edge of its cone
+    let (at, facing) = watching(map, player, |distance| distance > GUARD_VISION.range - 1.0);
+    let mut glimpsed = Harness::start(9, PlayMode::TurnBased, Restore::from(alone_with(saved.clone(), guard_at(at, facing, GuardState::Patrol))));
+    glimpsed.steps(1);
+    let state = glimpsed.guards().guards[0].state;
+    assert!(matches!(state, GuardState::Suspicious { at, .. } if at == player), "{:?}", state);
+
+    // close enough to make the player out, not yet within reach
+    let (at, facing) = watching(map, player, |distance| distance > 1.5 && distance <= GUARD_VISION.range - 1.0);
+    let mut spotted = Harness::start(9, PlayMode::TurnBased, Restore::from(alone_with(saved, guard_at(at, facing, GuardState::Patrol))));
+    spotted.steps(1);
+    assert_eq!(spotted.guards().guards[0].state, GuardState::Hunting { last_seen: player, lost: 0 });
+    assert_eq!(spotted.state(), GameState::Game);
+}
+
+#[test]
+fn a_suspicious_guard_goes_to_look_then_calms_down() {
+    let states = left_alone(9, |at| GuardState::Suspicious { at, wait: 2 });
+    assert_eq!(states, ["suspicious", "investigating", "patrol"]);
+}
+
+#[test]
+fn a_hunter_that_lost_the_player_searches_then_goes_back_to_patrol() {
+    let states = left_alone(9, |at| GuardState::Hunting { last_seen: at, lost: 0 });
+    assert_eq!(states, ["hunting", "investigating", "patrol"]);
+}
+
+#[test]
+fn the_elevator_takes_the_run_one_floor_up() {
+    let mut run = Harness::new(12, PlayMode::TurnBased);
+    run.steps(1);
+    let required = run.game().intel_required();
+
+    // short of intel the exit stays shut
+    let mut saved = on_the_exit(&run);
+    saved.game.floor_intel -= 1;
+    let mut short = Harness::start(12, PlayMode::TurnBased, Restore::from(saved));
+    short.steps(3);
+    assert_eq!((short.state(), short.game().floor), (GameState::Game, 1));
+
+    // and it only opens on the exit
+    let mut saved = run.snapshot();
+    saved.game.floor_intel = required;
+    let mut away = Harness::start(12, PlayMode::TurnBased, Restore::from(saved));
+    away.steps(3);
+    assert_eq!((away.state(), away.game().floor), (GameState::Game, 1));
+
+    // score and keycard come along, the intel starts over and more of it is needed
+    let mut saved = on_the_exit(&run);
+    saved.game.score = 7;
+    saved.game.player.keycard = true;
+    let mut up = Harness::start(12, PlayMode::TurnBased, Restore::from(saved));
+    up.steps(3);
+    assert_eq!(up.state(), GameState::Game);
+    let game = up.game();
+    assert_eq!(game.arrival, Arrival::Elevator);
+    assert_eq!(game.floor, 2);
+    assert_eq!(game.score, 7);
+    assert!(game.player.keycard);
+    assert_eq!(game.floor_intel, 0);
+    assert_eq!(game.intel_required(), required + 1);
+    assert_eq!((game.player.i, game.player.j), game.spawn);
+    assert_ne!(game.map, run.game().map);
+}
+
+#[test]
+fn intel_never_spawns_behind_a_locked_door() {
+    for seed in 0..5 {
+        let mut run = Harness::new(seed, PlayMode::TurnBased);
+        run.steps(1);
+        let mut saved = run.snapshot();
+        for (i, j, dir) in saved.game.map.door_edges() {
+            saved.game.map.set_door(i, j, dir, DoorState::Locked);
+        }
+        saved.game.bonus.active = false;
+        saved.game.bonus_spots.clear();
+        saved.game.bonus.spawned = 0;
+        // the cells the player walks to without going through a lock
+        let map = &saved.game.map;
+        let mut walkable = vec![(saved.game.player.i, saved.game.player.j)];
+        let mut next = 0;
+        while next < walkable.len() {
+            let (i, j) = walkable[next];
+            next += 1;
+            for (dir, cell) in map.passages(i, j) {
+                if map.door(i, j, dir) != Some(DoorState::Locked) && !walkable.contains(&(cell.i, cell.j)) {
+                    walkable.push((cell.i, cell.j));
+                }
+            }
+        }
+        assert!(walkable.len() < map.len(), "seed {}: nothing is locked away", seed);
+
+        let mut locked_in = Harness::start(seed, PlayMode::TurnBased, Restore::from(saved.clone()));
+        locked_in.steps(1);
+        let bonus = locked_in.game().bonus().expect("intel was due");
+        assert!(walkable.contains(&bonus), "seed {}: intel at {:?}", seed, bonus);
+    }
+}
@@ -0,0 +1,51 @@
+use errio::{difficulty::Difficulty, leaderboard::{self, Leaderboard, ScoreEntry, LEADERBOARD_SIZE}};
+
+mod common;
+use common::ScratchDir;
+
+fn entry(name: &str, score: i32, floor: u32, date: u64) -> ScoreEntry {
+    ScoreEntry { name: name.to_string(), score, date, floor, seed: 7, difficulty: Difficulty::Normal }
+}
+
+#[test]
+fn entries_are_ranked_and_capped() {
+    let mut board = Leaderboard::default();
+    for score in 0..LEADERBOARD_SIZE as i32 {
+        assert!(board.qualifies(score, 1));
+        board.insert(entry("agent", score, 1, 100));
+    }
+    assert_eq!(board.entries.len(), LEADERBOARD_SIZE);
+    assert!(!board.qualifies(0, 1));
+    // the higher floor breaks ties, then the older run
+    assert!(board.qualifies(0, 2));
+    assert_eq!(board.insert(entry("late", 5, 1, 200)), Some(5));
+    assert_eq!(board.insert(entry("high", 5, 3, 300)), Some(4));
+    assert_eq!(board.insert(entry("last", -1, 1, 300)), None);
+    assert_eq!(board.entries.len(), LEADERBOARD_SIZE);
+    assert_eq!(board.entries[0].score, 9);
+    assert!(board.entries.windows(2).all(|pair| pair[0].score >= pair[1].score));
+}
+
+#[test]
+fn corrupted_boards_are_set_aside() {
+    let scratch = ScratchDir::new("leaderboard-corrupted");
+    let path = scratch.file("corrupted.ron");
+    std::fs::write(&path, "(entries: [(name: \"agent\", score: ").unwrap();
+    assert_eq!(Leaderboard::load(&path), Leaderboard::default());
+    assert!(!path.exists());
+    assert!(scratch.file("corrupted.ron.corrupt").exists());
+
+    let mut board = Leaderboard::default();
+    board.insert(entry("agent", 4, 2, 1_600_000_000));
+    board.last_name = "agent".to_string();
+    board.save(&path).unwrap();
+    assert_eq!(Leaderboard::load(&path), board);
+    assert_eq!(Leaderboard::load(scratch.file("missing.ron")), Leaderboard::default());
+}
+
+#[test]
+fn dates_are_shown_in_utc() {
+    assert_eq!(leaderboard::date_string(0), "1970-01-01");
+    assert_eq!(leaderboard::date_string(951_782_400), "2000-02-29");
+    assert_eq!(leaderboard::date_string(1_700_000_000), "2023-11-14");
+}
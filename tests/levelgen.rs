@@ -0,0 +1,110 @@
+use std::str::FromStr;
+use rand::SeedableRng;
+use errio::{grid::{Grid, Direction}, levelgen::{self, LevelRng}, levelfile::LevelFile, pathfinding};
+
+#[test]
+fn exit_is_the_furthest_reachable_cell() {
+    for seed in 0..20 {
+        let (grid, _) = levelgen::generate_level(12, 16, 7, 3, Some(seed));
+        let spawn = levelgen::spawn_point(12, 16);
+        let exit = levelgen::exit_point(&grid, spawn);
+        let distances = pathfinding::distance_map(&grid, &[spawn]);
+        let furthest = distances.reachable().map(|(_, cost)| cost).max().unwrap();
+        assert_ne!(exit, spawn, "seed {}", seed);
+        assert_eq!(distances.cost(exit.0, exit.1), Some(furthest), "seed {}", seed);
+    }
+}
+
+#[test]
+fn level_files_check_the_exit() {
+    let (grid, _) = levelgen::generate_level(12, 16, 7, 3, Some(1));
+    let mut level = LevelFile {
+        width: 12,
+        length: 16,
+        spawn: levelgen::spawn_point(12, 16),
+        patrols: Vec::new(),
+        bonuses: Vec::new(),
+        exit: None,
+        cells: grid.into_cells(),
+    };
+    // older files without an exit still load
+    assert!(LevelFile::from_str(&level.to_string().unwrap()).is_ok());
+    level.exit = Some((12, 0));
+    assert!(LevelFile::from_str(&level.to_string().unwrap()).is_err());
+    level.exit = Some(level.spawn);
+    assert!(LevelFile::from_str(&level.to_string().unwrap()).is_err());
+    level.exit = Some((11, 15));
+    assert_eq!(LevelFile::from_str(&level.to_string().unwrap()).unwrap(), level);
+}
+
+#[test]
+fn generated_levels_are_connected() {
+    for seed in 0..2000 {
+        let (grid, _) = levelgen::generate_level(12, 16, 7, 3, Some(seed));
+        let report = levelgen::validate_level(&grid, levelgen::spawn_point(12, 16));
+        assert!(report.is_connected(), "seed {}: {:?} can't be reached", seed, report.unreachable);
+    }
+}
+
+#[test]
+fn sealed_rooms_are_refused_and_repaired() {
+    let (grid, _) = levelgen::generate_level(12, 16, 7, 3, Some(3));
+    // a spawn in the corner, then the corner walled off
+    let mut level = LevelFile {
+        width: 12,
+        length: 16,
+        spawn: (0, 0),
+        patrols: Vec::new(),
+        bonuses: Vec::new(),
+        exit: None,
+        cells: grid.into_cells(),
+    };
+    assert!(LevelFile::from_str(&level.to_string().unwrap()).is_ok());
+    level.cells[0].open_sides = [false; 4];
+    assert!(LevelFile::from_str(&level.to_string().unwrap()).is_err());
+    let mut sealed = Grid::new(12, 16, level.cells.clone());
+    assert_eq!(levelgen::validate_level(&sealed, level.spawn).unreachable.len(), 12 * 16 - 1);
+
+    let carved = levelgen::repair_level(&mut sealed, level.spawn, &mut LevelRng::seed_from_u64(3));
+    assert_eq!(carved, 1);
+    assert!(levelgen::validate_level(&sealed, level.spawn).is_connected());
+    level.cells = sealed.into_cells();
+    assert!(LevelFile::from_str(&level.to_string().unwrap()).is_ok());
+}
+
+#[test]
+fn repairs_stop_when_nothing_is_reachable() {
+    let (mut grid, _) = levelgen::generate_level(12, 16, 7, 3, Some(4));
+    assert_eq!(levelgen::validate_level(&grid, (12, 0)).unreachable.len(), 12 * 16);
+    assert_eq!(levelgen::repair_level(&mut grid, (12, 0), &mut LevelRng::seed_from_u64(4)), 0);
+}
+
+#[test]
+fn a_side_open_on_one_cell_only_is_a_wall_both_ways() {
+    let (grid, _) = levelgen::generate_level(12, 16, 7, 3, Some(5));
+    let mut cells = grid.into_cells();
+    // the corner cell keeps its east side open, the cell next to it walls it off
+    cells[0].open_sides = [false, true, false, false];
+    cells[1].open_sides[Direction::West.index()] = false;
+    let grid = Grid::new(12, 16, cells);
+    assert!(!grid.is_passage(0, 0, Direction::East));
+    assert!(!grid.is_passage(1, 0, Direction::West));
+    assert_eq!(levelgen::validate_level(&grid, (0, 0)).unreachable.len(), 12 * 16 - 1);
+}
+
+#[test]
+fn the_curated_mission_loads() {
+    let level = errio::levelfile::load_level("assets/levels/mission-01.ron").unwrap();
+    assert_eq!(level.exit, Some((7, 15)));
+}
+
+#[test]
+fn floors_of_neighbouring_seeds_differ() {
+    use rand::RngCore;
+    let draw = |mut rng: LevelRng| (0..4).map(|_| rng.next_u64()).collect::<Vec<_>>();
+    assert_eq!(draw(levelgen::floor_rng(8, 1)), draw(LevelRng::seed_from_u64(8)));
+    for seed in 0..50 {
+        assert_ne!(draw(levelgen::floor_rng(seed, 2)), draw(levelgen::floor_rng(seed + 1, 1)), "seed {}", seed);
+        assert_ne!(draw(levelgen::floor_rng(seed, 2)), draw(levelgen::floor_rng(seed, 3)), "seed {}", seed);
+    }
+}
@@ -0,0 +1,35 @@
+use errio::{grid::Direction, noise::{self, Noise}, pathfinding::DIST_BETWEEN};
+
+mod common;
+use common::{open_grid, close};
+
+#[test]
+fn noise_carries_its_radius_in_steps() {
+    let grid = open_grid(9, 1);
+    let noise = Noise { at: (4, 0), radius: 3 };
+    let heard: Vec<usize> = (0..9).filter(|&i| noise::hears(&grid, &noise, i, 0)).collect();
+    assert_eq!(heard, vec![1, 2, 3, 4, 5, 6, 7]);
+}
+
+#[test]
+fn noise_goes_around_walls() {
+    let mut grid = open_grid(3, 3);
+    // wall between the first two columns, open at the bottom
+    close(&mut grid, 0, 0, Direction::East);
+    close(&mut grid, 0, 1, Direction::East);
+    let noise = Noise { at: (0, 0), radius: 3 };
+    let reach = noise::noise_reach(&grid, &noise);
+    assert_eq!(reach.cost(1, 0), None);
+    assert_eq!(reach.cost(1, 2), Some(3 * DIST_BETWEEN));
+    assert!(!noise::hears(&grid, &noise, 1, 1));
+}
+
+#[test]
+fn doors_muffle_noise() {
+    let mut grid = open_grid(6, 1);
+    let noise = Noise { at: (0, 0), radius: 4 };
+    assert!(noise::hears(&grid, &noise, 4, 0));
+    grid.get_mut(1, 0).unwrap().doors[Direction::East.index()] = true;
+    assert!(noise::hears(&grid, &noise, 2, 0));
+    assert!(!noise::hears(&grid, &noise, 3, 0));
+}
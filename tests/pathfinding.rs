@@ -0,0 +1,164 @@
+use errio::{DoorState, grid::{Grid, Direction}, levelgen, pathfinding::{Pathfinder, Heuristic, UniformCost, TerrainCost, ReservationTable}};
+
+const HEURISTICS: [Heuristic; 4] = [Heuristic::Manhattan, Heuristic::Chebyshev, Heuristic::Octile, Heuristic::Zero];
+
+mod common;
+use common::{open_grid, close};
+
+fn cost(pathfinder: &Pathfinder, grid: &Grid, from: (usize, usize), to: (usize, usize)) -> Option<i32> {
+    let start = grid.get(from.0, from.1).unwrap();
+    let end = grid.get(to.0, to.1).unwrap();
+    pathfinder.find_path_with_cost(grid, start, end).map(|(_, cost)| cost)
+}
+
+#[test]
+fn orthogonal_paths_are_manhattan_on_open_ground() {
+    let grid = open_grid(8, 6);
+    for heuristic in HEURISTICS {
+        let pathfinder = Pathfinder::new(&UniformCost).with_heuristic(heuristic);
+        let (path, total) = pathfinder.find_path_with_cost(&grid, &grid.cells()[0], grid.get(7, 5).unwrap()).unwrap();
+        assert_eq!(path.len(), 13, "{:?}", heuristic);
+        assert_eq!(total, 120, "{:?}", heuristic);
+        assert_eq!((path[0].i, path[0].j), (7, 5));
+        assert_eq!((path[12].i, path[12].j), (0, 0));
+    }
+}
+
+#[test]
+fn path_goes_around_walls() {
+    let mut grid = open_grid(5, 5);
+    // wall between rows 1 and 2, with a gap at i = 4
+    for i in 0..4 {
+        close(&mut grid, i, 1, Direction::South);
+    }
+    for heuristic in HEURISTICS {
+        let pathfinder = Pathfinder::new(&UniformCost).with_heuristic(heuristic);
+        assert_eq!(cost(&pathfinder, &grid, (0, 0), (0, 4)), Some(4 * 10 + 4 * 10 + 4 * 10), "{:?}", heuristic);
+    }
+}
+
+#[test]
+fn sealed_goal_has_no_path() {
+    let mut grid = open_grid(4, 4);
+    for dir in Direction::ALL {
+        close(&mut grid, 2, 2, dir);
+    }
+    let pathfinder = Pathfinder::new(&UniformCost);
+    assert_eq!(cost(&pathfinder, &grid, (0, 0), (2, 2)), None);
+}
+
+#[test]
+fn diagonal_paths_are_octile_on_open_ground() {
+    let grid = open_grid(8, 6);
+    for heuristic in [Heuristic::Chebyshev, Heuristic::Octile, Heuristic::Zero] {
+        let pathfinder = Pathfinder::new(&UniformCost).with_heuristic(heuristic).with_diagonal(true);
+        let (path, total) = pathfinder.find_path_with_cost(&grid, &grid.cells()[0], grid.get(7, 5).unwrap()).unwrap();
+        // 5 diagonal and 2 straight steps
+        assert_eq!(path.len(), 8, "{:?}", heuristic);
+        assert_eq!(total, 5 * 14 + 2 * 10, "{:?}", heuristic);
+    }
+}
+
+#[test]
+fn diagonal_steps_do_not_cut_corners() {
+    let mut grid = open_grid(2, 2);
+    close(&mut grid, 0, 0, Direction::East);
+    let pathfinder = Pathfinder::new(&UniformCost).with_diagonal(true);
+    // (0, 0) -> (1, 1) has to go around through (0, 1)
+    assert_eq!(cost(&pathfinder, &grid, (0, 0), (1, 1)), Some(20));
+
+    let mut grid = open_grid(2, 2);
+    grid.get_mut(1, 0).unwrap().doors[Direction::South.index()] = true;
+    let pathfinder = Pathfinder::new(&UniformCost).with_diagonal(true);
+    // door frames block diagonals as well
+    assert_eq!(cost(&pathfinder, &grid, (0, 0), (1, 1)), Some(20));
+}
+
+#[test]
+fn heuristics_agree_with_dijkstra_on_generated_levels() {
+    for seed in 0..50 {
+        let (grid, _) = levelgen::generate_level(12, 16, 7, 3, Some(seed));
+        for diagonal in [false, true] {
+            let terrain = TerrainCost::player();
+            let dijkstra = Pathfinder::new(&terrain).with_heuristic(Heuristic::Zero).with_diagonal(diagonal);
+            let admissible: &[Heuristic] = if diagonal { &[Heuristic::Chebyshev, Heuristic::Octile] } else { &HEURISTICS };
+            for (a, b) in [(0, 191), (17, 150), (95, 3), (60, 61)] {
+                let expected = dijkstra.find_path_with_cost(&grid, &grid[a], &grid[b]).map(|(_, cost)| cost);
+                for &heuristic in admissible {
+                    let pathfinder = dijkstra.with_heuristic(heuristic);
+                    let found = pathfinder.find_path_with_cost(&grid, &grid[a], &grid[b]).map(|(_, cost)| cost);
+                    assert_eq!(found, expected, "seed {} {:?} diagonal {}", seed, heuristic, diagonal);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn distance_map_matches_point_to_point_costs() {
+    let (grid, _) = levelgen::generate_level(12, 16, 7, 3, Some(3));
+    let terrain = TerrainCost::guard();
+    let pathfinder = Pathfinder::new(&terrain);
+    let sources = [(0, 0), (11, 15)];
+    let distances = pathfinder.distance_map(&grid, &sources);
+    for cell in grid.iter() {
+        let nearest = sources.iter()
+            .filter_map(|&source| cost(&pathfinder, &grid, source, (cell.i, cell.j)))
+            .min();
+        assert_eq!(distances.cost(cell.i, cell.j), nearest);
+        if let Some(path) = distances.path_to(&grid, cell.i, cell.j) {
+            let source = path.last().unwrap();
+            assert!(sources.contains(&(source.i, source.j)));
+        }
+    }
+    assert!(distances.within(0).all(|cell| sources.contains(&cell)));
+}
+
+#[test]
+fn timed_paths_wait_for_reserved_cells() {
+    let grid = open_grid(3, 1);
+    let mut reservations = ReservationTable::default();
+    // agent 0 stands in the middle of the corridor on tick 1
+    reservations.reserve(1, 0, 1, 0);
+    let pathfinder = Pathfinder::new(&UniformCost);
+    let path = pathfinder.find_path_timed(&grid, grid.get(0, 0).unwrap(), grid.get(2, 0).unwrap(), 0, &reservations, 1).unwrap();
+    let cells: Vec<(usize, usize)> = path.iter().rev().map(|cell| (cell.i, cell.j)).collect();
+    assert_eq!(cells, vec![(0, 0), (0, 0), (1, 0), (2, 0)]);
+}
+
+#[test]
+fn timed_paths_do_not_swap_through_each_other() {
+    let grid = open_grid(2, 1);
+    let mut reservations = ReservationTable::default();
+    let pathfinder = Pathfinder::new(&UniformCost);
+    let first = pathfinder.find_path_timed(&grid, grid.get(0, 0).unwrap(), grid.get(1, 0).unwrap(), 0, &reservations, 0).unwrap();
+    reservations.reserve_path(0, &first, 0, 4);
+    // the only way would be swapping places with agent 0
+    assert_eq!(pathfinder.find_path_timed(&grid, grid.get(1, 0).unwrap(), grid.get(0, 0).unwrap(), 0, &reservations, 1), None);
+}
+
+#[test]
+fn shut_doors_cost_opening_and_locked_ones_need_a_key() {
+    let mut grid = open_grid(3, 1);
+    grid.get_mut(0, 0).unwrap().doors[Direction::East.index()] = true;
+    let grid = Grid::new(3, 1, grid.into_cells());
+    let player = TerrainCost::player();
+    let guard = TerrainCost::guard();
+    let walk = |grid: &Grid, cost: &TerrainCost| cost_with(grid, cost, (0, 0), (2, 0));
+    // doorway plus two floor steps
+    assert_eq!(walk(&grid, &player), Some(25));
+
+    let mut closed = grid.clone();
+    assert!(closed.set_door(1, 0, Direction::West, DoorState::Closed));
+    assert_eq!(closed.door(0, 0, Direction::East), Some(DoorState::Closed));
+    assert_eq!(walk(&closed, &player), Some(35));
+
+    let mut locked = grid.clone();
+    locked.set_door(0, 0, Direction::East, DoorState::Locked);
+    assert_eq!(walk(&locked, &player), None);
+    assert_eq!(walk(&locked, &guard), Some(45));
+}
+
+fn cost_with(grid: &Grid, model: &TerrainCost, from: (usize, usize), to: (usize, usize)) -> Option<i32> {
+    cost(&Pathfinder::new(model), grid, from, to)
+}
@@ -0,0 +1,57 @@
+use errio::{grid::Direction, sim::PlayerInput, replay::{Replay, Playback}};
+
+mod common;
+use common::{ScratchDir, new_run};
+
+// Input of every step of a short walk: a few steps east, a door, a held run north, a clicked route
+fn walk() -> Vec<PlayerInput> {
+    let east = PlayerInput { steer: vec![Direction::East], ..Default::default() };
+    let run_north = PlayerInput { steer: vec![Direction::North], run: true, ..Default::default() };
+    let mut steps = vec![PlayerInput::default(); 3];
+    steps.extend(vec![east.clone(); 20]);
+    steps.push(PlayerInput { door: true, ..east });
+    steps.extend(vec![PlayerInput::default(); 5]);
+    steps.push(PlayerInput { end_turn: true, ..run_north.clone() });
+    steps.extend(vec![run_north; 30]);
+    steps.push(PlayerInput { route: Some(vec![(3, 4), (3, 5)]), ..Default::default() });
+    steps.extend(vec![PlayerInput::default(); 10]);
+    steps
+}
+
+#[test]
+fn played_back_input_matches_every_step() {
+    let steps = walk();
+    let mut replay = Replay::new(new_run(5), (12, 16));
+    for (step, input) in steps.iter().enumerate() {
+        replay.record(step as u64 + 1, input);
+    }
+    assert_eq!(replay.steps, steps.len() as u64);
+    // held keys are only written down when they change
+    assert!(replay.inputs.len() < 10, "{} changes kept", replay.inputs.len());
+
+    let mut playback = Playback::new(replay);
+    for (step, input) in steps.iter().enumerate() {
+        assert_eq!(&playback.input(step as u64 + 1), input, "step {}", step + 1);
+    }
+}
+
+#[test]
+fn replays_survive_a_save_and_load() {
+    let scratch = ScratchDir::new("replay-load");
+    let path = scratch.file("replay.ron");
+    let mut replay = Replay::new(new_run(9), (14, 18));
+    for (step, input) in walk().iter().enumerate() {
+        replay.record(step as u64 + 1, input);
+    }
+    replay.end = Some(new_run(9));
+    replay.save(&path).unwrap();
+    let loaded = Replay::load(&path).unwrap();
+    assert_eq!(loaded, replay);
+    let playback = Playback::new(loaded);
+    assert!(playback.matches(replay.steps, &new_run(9)));
+    assert!(!playback.matches(replay.steps, &new_run(10)));
+    assert!(!playback.matches(replay.steps - 1, &new_run(9)));
+
+    std::fs::write(&path, "(version: 1, seed: ").unwrap();
+    assert!(Replay::load(&path).is_err());
+}
@@ -0,0 +1,79 @@
+use rand::Rng;
+use errio::{grid::Direction, fog::PLAYER_VISION, difficulty::Difficulty, guard::{GuardSnapshot, GuardState, GUARD_VISION}, game::BonusSnapshot, turn::{Turn, PlayMode}, save::{RunSnapshot, Restore, SAVE_VERSION}};
+
+mod common;
+use common::{ScratchDir, new_run};
+
+// A run some way into its second floor
+fn run() -> RunSnapshot {
+    let mut run = new_run(3);
+    let (game, guards) = (&mut run.game, &mut run.guards);
+    let (map, spawn) = (&game.map, game.spawn);
+    game.player.facing = Direction::South;
+    game.player.keycard = true;
+    game.player.route = vec![(2, 3), (2, 2)];
+    game.score = 5;
+    game.seed = 42;
+    game.difficulty = Difficulty::Hard;
+    game.floor = 2;
+    game.floor_intel = 1;
+    game.patrols = vec![vec![(1, 1), (4, 1)]];
+    game.bonus_spots = vec![(3, 3)];
+    game.bonus = BonusSnapshot { active: true, i: 3, j: 3, spawned: 1, collected_on: 0, discovered: false, respawn: 1.5 };
+    game.rng.gen::<u64>();
+    guards.guards = vec![GuardSnapshot {
+        id: 1,
+        i: 4,
+        j: 4,
+        rotation: Direction::West,
+        current_path: Some(vec![*map.get(4, 5).unwrap(), *map.get(4, 4).unwrap()]),
+        pp: 1,
+        patrol_points: vec![(4, 4), (8, 4)],
+        state: GuardState::Investigating { around: (6, 6), target: (5, 6), searches_left: 2, calm: 0 },
+        vision: GUARD_VISION,
+        model: 0,
+    }];
+    guards.tick = 11;
+    guards.reservations.reserve(4, 5, 12, 1);
+    guards.next_id = 2;
+    guards.in_view = vec![1];
+    guards.knocked_out = 1;
+    guards.profile = Difficulty::Hard.profile();
+    let mut turn = Turn::new(PlayMode::TurnBased);
+    turn.spend(2);
+    run.turn = turn.snapshot();
+    run.fog.reveal(&run.game.map, spawn, &PLAYER_VISION);
+    run
+}
+
+#[test]
+fn runs_survive_a_save_and_load() {
+    let scratch = ScratchDir::new("save-load");
+    let path = scratch.file("run.ron");
+    let saved = run();
+    saved.save(&path).unwrap();
+    let loaded = RunSnapshot::load(&path).unwrap();
+    assert_eq!(loaded, saved);
+    // the random source carries on where it stopped
+    let (mut saved_rng, mut loaded_rng) = (saved.game.rng.clone(), loaded.game.rng.clone());
+    assert_eq!(saved_rng.gen::<u64>(), loaded_rng.gen::<u64>());
+    assert_eq!(Turn::restore(&loaded.turn).snapshot(), saved.turn);
+
+    let restore = Restore::from(loaded);
+    assert_eq!(restore.game, Some(saved.game));
+    assert_eq!(restore.guards, Some(saved.guards));
+    assert_eq!(restore.fog, Some(saved.fog));
+}
+
+#[test]
+fn foreign_saves_are_refused() {
+    let scratch = ScratchDir::new("save-foreign");
+    let path = scratch.file("old.ron");
+    let mut saved = run();
+    saved.version = SAVE_VERSION + 1;
+    saved.save(&path).unwrap();
+    assert!(RunSnapshot::load(&path).is_err());
+    std::fs::write(&path, "(version: 1, game: (").unwrap();
+    assert!(RunSnapshot::load(&path).is_err());
+    assert!(RunSnapshot::load(scratch.file("missing.ron")).is_err());
+}
@@ -0,0 +1,35 @@
+use errio::{difficulty::Difficulty, settings::{Settings, WindowModeSetting}, turn::PlayMode};
+
+mod common;
+use common::ScratchDir;
+
+#[test]
+fn settings_survive_a_save_and_load() {
+    let scratch = ScratchDir::new("settings-load");
+    let path = scratch.file("saved.ron");
+    let mut settings = Settings::default();
+    settings.display.window_mode = WindowModeSetting::Borderless;
+    settings.display.shadows = true;
+    settings.sound.master = 75;
+    settings.sound.music = 25;
+    settings.sound.effects = 0;
+    settings.gameplay.difficulty = Difficulty::Hard;
+    settings.gameplay.mode = PlayMode::RealTime;
+    settings.save(&path).unwrap();
+    assert_eq!(Settings::load(&path), settings);
+}
+
+#[test]
+fn missing_or_broken_settings_fall_back_to_defaults() {
+    let scratch = ScratchDir::new("settings-fallback");
+    assert_eq!(Settings::load(scratch.file("missing.ron")), Settings::default());
+    let broken = scratch.file("broken.ron");
+    std::fs::write(&broken, "(display: (vsync: maybe").unwrap();
+    assert_eq!(Settings::load(&broken), Settings::default());
+    // fields left out keep their defaults
+    let partial = scratch.file("partial.ron");
+    std::fs::write(&partial, "(gameplay: (difficulty: Easy))").unwrap();
+    let settings = Settings::load(&partial);
+    assert_eq!(settings.gameplay.difficulty, Difficulty::Easy);
+    assert_eq!(settings.display, Settings::default().display);
+}
@@ -0,0 +1,101 @@
+use errio::{grid::{Grid, Direction}, vision::{self, VisionParams}};
+
+mod common;
+use common::{open_grid, close};
+
+#[test]
+fn cone_looks_only_ahead() {
+    let grid = open_grid(9, 9);
+    let seen = vision::visible_cells(&grid, (4, 4), Direction::East, &VisionParams::cone(3.0, 90.0));
+    assert!(seen.contains(&(4, 4)));
+    assert!(seen.contains(&(7, 4)));
+    // the edges of a 90 degree cone are the diagonals
+    assert!(seen.contains(&(6, 2)));
+    assert!(seen.contains(&(6, 6)));
+    assert!(!seen.contains(&(5, 2)));
+    assert!(!seen.contains(&(8, 4)));
+    assert!(seen.iter().all(|&(i, _)| i >= 4));
+}
+
+#[test]
+fn all_round_vision_is_a_disc() {
+    let grid = open_grid(9, 9);
+    let seen = vision::visible_cells(&grid, (4, 4), Direction::North, &VisionParams::all_round(2.0));
+    // 13 cells within a radius of 2
+    assert_eq!(seen.len(), 13);
+    assert!(seen.contains(&(2, 4)) && seen.contains(&(4, 6)) && seen.contains(&(5, 5)));
+    assert!(!seen.contains(&(6, 6)));
+}
+
+#[test]
+fn walls_block_the_view_behind_them() {
+    let mut grid = open_grid(6, 3);
+    // wall between columns 2 and 3 on the middle row only
+    close(&mut grid, 2, 1, Direction::East);
+    let params = VisionParams::cone(5.0, 90.0);
+    assert!(!vision::can_see(&grid, (0, 1), Direction::East, &params, (3, 1)));
+    assert!(!vision::can_see(&grid, (0, 1), Direction::East, &params, (5, 1)));
+    assert!(vision::can_see(&grid, (0, 1), Direction::East, &params, (2, 1)));
+    // the rows above and below are open
+    assert!(vision::can_see(&grid, (0, 0), Direction::East, &params, (5, 0)));
+}
+
+#[test]
+fn the_crossed_edge_is_checked_not_the_target_cell() {
+    let mut grid = open_grid(4, 1);
+    // the far cell is closed on its east side, that doesn't hide it
+    grid.get_mut(3, 0).unwrap().open_sides[Direction::East.index()] = false;
+    let params = VisionParams::cone(4.0, 90.0);
+    assert!(vision::can_see(&grid, (0, 0), Direction::East, &params, (3, 0)));
+    // but a wall the line actually crosses does
+    close(&mut grid, 1, 0, Direction::East);
+    assert!(!vision::can_see(&grid, (0, 0), Direction::East, &params, (3, 0)));
+}
+
+#[test]
+fn corners_need_one_open_way_around() {
+    let mut grid = open_grid(2, 2);
+    close(&mut grid, 0, 0, Direction::East);
+    assert!(vision::line_of_sight(&grid, (0, 0), (1, 1)));
+    close(&mut grid, 0, 0, Direction::South);
+    assert!(!vision::line_of_sight(&grid, (0, 0), (1, 1)));
+}
+
+#[test]
+fn line_of_sight_is_symmetric_on_generated_levels() {
+    for seed in 0..20 {
+        let (grid, _) = errio::levelgen::generate_level(12, 16, 7, 3, Some(seed));
+        for a in (0..grid.len()).step_by(7) {
+            for b in (0..grid.len()).step_by(11) {
+                let (from, to) = ((grid[a].i, grid[a].j), (grid[b].i, grid[b].j));
+                assert_eq!(vision::line_of_sight(&grid, from, to), vision::line_of_sight(&grid, to, from), "seed {} {:?} {:?}", seed, from, to);
+            }
+        }
+    }
+}
+
+#[test]
+fn upcoming_tiles_follow_the_planned_walk() {
+    let grid = open_grid(9, 3);
+    let params = VisionParams::cone(2.0, 90.0);
+    let view = vision::WatcherView { at: (0, 1), facing: Direction::East, ahead: vec![(1, 1), (2, 1)] };
+    let tiles = vision::danger_tiles(&grid, &[view], &params);
+    assert!(tiles.watched.contains(&(2, 1)));
+    assert!(!tiles.watched.contains(&(4, 1)));
+    // seen from (2, 1) after two steps east
+    assert!(tiles.upcoming.contains(&(3, 1)) && tiles.upcoming.contains(&(4, 1)));
+    assert!(tiles.upcoming.is_disjoint(&tiles.watched));
+}
+
+#[test]
+fn closed_doors_block_the_view() {
+    let mut cells = open_grid(4, 1).into_cells();
+    cells[1].doors[Direction::East.index()] = true;
+    cells[1].door_states[Direction::East.index()] = errio::DoorState::Closed;
+    let mut grid = Grid::new(4, 1, cells);
+    // the door is shared with the cell on the other side
+    assert_eq!(grid.door(2, 0, Direction::West), Some(errio::DoorState::Closed));
+    assert!(!vision::line_of_sight(&grid, (0, 0), (3, 0)));
+    grid.set_door(2, 0, Direction::West, errio::DoorState::Open);
+    assert!(vision::line_of_sight(&grid, (0, 0), (3, 0)));
+}